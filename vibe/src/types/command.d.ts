@@ -1,9 +1,65 @@
-import { Track, Pattern, Slider, Event } from "./models";
+import {
+  Track,
+  Pattern,
+  Slider,
+  Event,
+  Target,
+  Route,
+  MinOscMessage,
+  MessageKind,
+  DeliveryPolicy,
+  DeliveryPolicies,
+  QueueStats,
+  TickLateness,
+  TempoRamp,
+  ClockSource,
+  Metronome,
+  TimeSignature,
+  MonitorEntry,
+  TransformRule,
+  DmxMapping,
+  SinkOptions,
+  MidiMapping,
+} from "./models";
 
 export type ServerCommand =
   | { action: "SetProjectName"; payload: { name: string } }
   | { action: "CommChangeAddr"; payload: { addr: string } }
   | { action: "CtrlChangeContext"; payload: { context: string | null } }
+  // LYN: Target
+  | { action: "CommTargetAdd"; payload: { name: string; addr: string } }
+  | { action: "CommTargetDelete"; payload: { name: string } }
+  | { action: "CommTargetChangeAddr"; payload: { name: string; addr: string } }
+  | {
+      action: "CommTargetSetTiming";
+      payload: { name: string; lookahead_ms: number; latency_ms: number };
+    }
+  | {
+      action: "CommTargetSetFallbacks";
+      payload: { name: string; fallbacks: string[]; grace_ms: number };
+    }
+  | {
+      action: "CommTargetSetEcho";
+      payload: { name: string; echo_path: string | null; auto_latency: boolean };
+    }
+  | {
+      action: "CommTargetSetTransforms";
+      payload: { name: string; transforms: TransformRule[] };
+    }
+  | { action: "CommRouteAdd"; payload: { route: Route } }
+  | { action: "CommRouteDelete"; payload: { route: Route } }
+  | {
+      action: "CommSetDelivery";
+      payload: { kind: MessageKind; policy: DeliveryPolicy };
+    }
+  | { action: "CommSetDmxMappings"; payload: { mappings: DmxMapping[] } }
+  | { action: "CommSetMidiMappings"; payload: { mappings: MidiMapping[] } }
+  | {
+      action: "CommSetSinkOptions";
+      payload: { scheme: string; options: SinkOptions };
+    }
+  | { action: "CommMonitorSubscribe"; payload: { prefix: string | null } }
+  | { action: "CommMonitorUnsubscribe" }
   // LYN: Track
   | { action: "TrackAdd"; payload: { name: string } }
   | { action: "TrackDelete"; payload: { name: string } }
@@ -22,6 +78,17 @@ export type ServerCommand =
   | { action: "TickerPause" }
   | { action: "TickerStop" }
   | { action: "TickerSetBpm"; payload: { bpm: number } }
+  | { action: "TickerSetResolution"; payload: { resolution: number } }
+  | {
+      action: "TickerSetTimeSignature";
+      payload: { time_signature: TimeSignature };
+    }
+  | { action: "TickerRamp"; payload: { ramp: TempoRamp } }
+  | { action: "TickerTap" }
+  | { action: "TickerNudge"; payload: { ms: number } }
+  | { action: "TickerSetLink"; payload: { enabled: boolean } }
+  | { action: "TickerSetClock"; payload: { clock: ClockSource } }
+  | { action: "TickerSetMetronome"; payload: { metronome: Metronome } }
   // LYN: Event
   | { action: "EventAdd"; payload: { name: string } }
   | { action: "EventDelete"; payload: { name: string } }
@@ -32,24 +99,89 @@ export type ServerCommand =
   | { action: "SliderDelete"; payload: { name: string } }
   | { action: "SliderEdit"; payload: { name: string; slider: Slider } }
   | { action: "SliderSetVal"; payload: { name: string; val: number } }
+  | { action: "SliderSetRate"; payload: { rate_hz: number } }
   // LYN: Request
   | { action: "RequestTickerBpm" }
+  | { action: "RequestTickerResolution" }
+  | { action: "RequestTickerTimeSignature" }
   | { action: "RequestTickerPlaying" }
   | { action: "RequestTickerTick" }
+  | { action: "RequestTickerLateness" }
+  | { action: "RequestTickerLink" }
+  | { action: "RequestTickerClock" }
+  | { action: "RequestTickerMetronome" }
   | { action: "RequestProjectName" }
   | { action: "RequestCommAddr" }
   | { action: "RequestCommStatus" }
+  | { action: "RequestCommTargets" }
+  | { action: "RequestCommRoutes" }
+  | { action: "RequestCommDelivery" }
+  | { action: "RequestCommQueues" }
+  | { action: "RequestCommDmxMappings" }
+  | { action: "RequestCommMidiMappings" }
+  | { action: "RequestCommSinkOptions" }
   | { action: "RequestCtrlContext" }
   | { action: "RequestAllTracks" }
   | { action: "RequestAllPatterns" }
   | { action: "RequestAllEvents" }
-  | { action: "RequestAllSliders" };
+  | { action: "RequestAllSliders" }
+  | { action: "RequestSliderRate" };
 
 export type ClientCommand =
   | { action: "ProjectNameUpdated"; payload: { name: string } }
   | { action: "CommAddrChanged"; payload: { addr: string } }
   | { action: "CommStatusChanged"; payload: { established: boolean } }
   | { action: "CtrlContextChanged"; payload: { context: string | null } }
+  // LYN: Target
+  | { action: "CommTargetAdded"; payload: { name: string; target: Target } }
+  | { action: "CommTargetDeleted"; payload: { name: string } }
+  | { action: "CommTargetAddrChanged"; payload: { name: string; addr: string } }
+  | {
+      action: "CommTargetStatusChanged";
+      payload: { name: string; established: boolean };
+    }
+  | {
+      action: "CommTargetTimingChanged";
+      payload: { name: string; lookahead_ms: number; latency_ms: number };
+    }
+  | {
+      action: "CommTargetFallbacksChanged";
+      payload: { name: string; fallbacks: string[]; grace_ms: number };
+    }
+  | {
+      action: "CommTargetActiveChanged";
+      payload: { name: string; addr: string; primary: boolean };
+    }
+  | {
+      action: "CommTargetEchoChanged";
+      payload: { name: string; echo_path: string | null; auto_latency: boolean };
+    }
+  | {
+      action: "CommTargetLatency";
+      payload: { name: string; rtt_ms: number; jitter_ms: number };
+    }
+  | {
+      action: "CommTargetTransformsChanged";
+      payload: { name: string; transforms: TransformRule[] };
+    }
+  | { action: "CommRouteAdded"; payload: { route: Route } }
+  | { action: "CommRouteDeleted"; payload: { route: Route } }
+  | {
+      action: "CommMessageReceived";
+      payload: { target: string; msg: MinOscMessage };
+    }
+  | {
+      action: "CommDeliveryUpdated";
+      payload: { kind: MessageKind; policy: DeliveryPolicy };
+    }
+  | { action: "CommDmxMappingsUpdated"; payload: { mappings: DmxMapping[] } }
+  | { action: "CommMidiMappingsUpdated"; payload: { mappings: MidiMapping[] } }
+  | {
+      action: "CommSinkOptionsUpdated";
+      payload: { scheme: string; options: SinkOptions };
+    }
+  | { action: "CommQueueUpdated"; payload: { name: string; stats: QueueStats } }
+  | { action: "CommMonitorEntry"; payload: { entry: MonitorEntry } }
   // LYN: Track
   | { action: "TrackAdded"; payload: { name: string; track: Track } }
   | { action: "TrackDeleted"; payload: { name: string } }
@@ -73,19 +205,70 @@ export type ClientCommand =
   | { action: "SliderDeleted"; payload: { name: string } }
   | { action: "SliderEdited"; payload: { name: string; slider: Slider } }
   | { action: "SliderValSet"; payload: { name: string; val: number } }
+  | { action: "SliderRateUpdated"; payload: { rate_hz: number } }
   // LYN: Ticker
   | { action: "TickerPlaying" }
   | { action: "TickerPaused" }
   | { action: "TickerStopped" }
-  | { action: "TickerTick"; payload: { tick: number; max: usize } }
-  | { action: "TickerBpmUpdated"; payload: { bpm: number } }
+  | {
+      action: "TickerTick";
+      payload: { tick: number; max: usize; bar: number };
+    }
+  | {
+      action: "TickerBpmUpdated";
+      payload: { bpm: number; time_signature: TimeSignature };
+    }
+  | {
+      action: "TickerRampProgress";
+      payload: { bpm: number; target_bpm: number; progress: number };
+    }
+  | { action: "TickerResolutionUpdated"; payload: { resolution: number } }
+  | {
+      action: "TickerLinkUpdated";
+      payload: { enabled: boolean; peers: number };
+    }
+  | { action: "TickerClockUpdated"; payload: { clock: ClockSource } }
+  | { action: "TickerMetronomeUpdated"; payload: { metronome: Metronome } }
+  | { action: "TickerCountIn"; payload: { bars_left: number } }
   // LYN: Response
   | { action: "ResponseTickerBpm"; payload: { bpm: number } }
+  | { action: "ResponseTickerResolution"; payload: { resolution: number } }
+  | {
+      action: "ResponseTickerTimeSignature";
+      payload: { time_signature: TimeSignature };
+    }
   | { action: "ResponseTickerPlaying"; payload: { playing: boolean } }
   | { action: "ResponseTickerTick"; payload: { tick: number; max: usize } }
+  | { action: "ResponseTickerLateness"; payload: { lateness: TickLateness } }
+  | {
+      action: "ResponseTickerLink";
+      payload: { enabled: boolean; peers: number };
+    }
+  | { action: "ResponseTickerClock"; payload: { clock: ClockSource } }
+  | { action: "ResponseTickerMetronome"; payload: { metronome: Metronome } }
   | { action: "ResponseProjectName"; payload: { name: string } }
   | { action: "ResponseCommAddr"; payload: { addr: string } }
   | { action: "ResponseCommStatus"; payload: { established: boolean } }
+  | {
+      action: "ResponseCommTargets";
+      payload: {
+        targets: Record<string, Target>;
+        statuses: Record<string, boolean>;
+        actives: Record<string, string>;
+      };
+    }
+  | { action: "ResponseCommRoutes"; payload: { routes: Route[] } }
+  | { action: "ResponseCommDelivery"; payload: { delivery: DeliveryPolicies } }
+  | {
+      action: "ResponseCommQueues";
+      payload: { queues: Record<string, QueueStats> };
+    }
+  | { action: "ResponseCommDmxMappings"; payload: { mappings: DmxMapping[] } }
+  | { action: "ResponseCommMidiMappings"; payload: { mappings: MidiMapping[] } }
+  | {
+      action: "ResponseCommSinkOptions";
+      payload: { options: Record<string, SinkOptions> };
+    }
   | { action: "ResponseCtrlContext"; payload: { context: string | null } }
   | { action: "ResponseAllTracks"; payload: { tracks: Record<string, Track> } }
   | {
@@ -97,6 +280,7 @@ export type ClientCommand =
       action: "ResponseAllSliders";
       payload: { sliders: Record<string, Slider> };
     }
+  | { action: "ResponseSliderRate"; payload: { rate_hz: number } }
   | {
       action: "Notify";
       payload: { severity: Severity; summary: string; detail: string };
@@ -1,8 +1,15 @@
-export type Page<T> = [T, T, T, T];
+// one beat, as many steps as the resolution
+export type Page<T> = T[];
+
+export type TimeSignature = {
+  beats: number;
+  unit: number;
+};
 
 export type Pattern = {
   name: string;
   page_count: number;
+  resolution: number | null;
   midi_path: string;
   midi_codes: Page<number | null>[];
   messages: Messages[];
@@ -26,7 +33,7 @@ export type Event = {
   name: string;
   path: string;
   shortcut: string | null;
-  payload: MinOscArg;
+  payload: MinOscArg[];
   color: string | null;
 };
 
@@ -37,13 +44,133 @@ export type Slider = {
   max: number;
   min: number;
   color: string | null;
+  feedback: boolean;
 };
 
 export type MinOscMessage = {
   path: string;
-  arg: MinOscArg;
+  args: MinOscArg[];
 };
 
 export type MinOscArg =
+  | { type: "Int"; value: number }
   | { type: "Float"; value: number }
-  | { type: "String"; value: string };
+  | { type: "String"; value: string }
+  | { type: "Bool"; value: boolean }
+  | { type: "Nil" }
+  | { type: "Blob"; value: number[] }
+  | { type: "Long"; value: number }
+  | { type: "Double"; value: number };
+
+export type Target = {
+  name: string;
+  addr: string;
+  lookahead_ms: number;
+  latency_ms: number;
+  fallbacks: string[];
+  failover_grace_ms: number;
+  echo_path: string | null;
+  auto_latency: boolean;
+  transforms: TransformRule[];
+};
+
+export type ArgType = "Int" | "Float" | "String" | "Bool" | "Long" | "Double";
+
+export type TransformRule = { path: string } & (
+  | { op: "rewrite"; to: string }
+  | {
+      op: "scale";
+      arg: number | null;
+      scale: number;
+      offset: number;
+      min: number | null;
+      max: number | null;
+    }
+  | { op: "convert"; arg: number | null; to: ArgType }
+  | { op: "drop" }
+);
+
+export type DmxMapping = {
+  path: string;
+  universe: number;
+  channel: number;
+  min: number;
+  max: number;
+};
+
+export type SinkOptions = {
+  default_port: number | null;
+  bundles: boolean;
+};
+
+export type MidiMapping = {
+  path: string;
+  channel: number;
+  velocity: number;
+  length_ms: number;
+};
+
+export type Route = {
+  prefix: string;
+  target: string;
+};
+
+export type MessageKind = "pattern" | "slider" | "event";
+
+export type DeliveryPolicy = "drop" | "latest" | "replay";
+
+export type DeliveryPolicies = Record<MessageKind, DeliveryPolicy>;
+
+export type QueueStats = {
+  depth: number;
+  dropped: number;
+};
+
+export type TickLateness = {
+  last_us: number;
+  mean_us: number;
+  max_us: number;
+  ticks: number;
+};
+
+export type RampUnit = "beats" | "bars";
+
+export type RampCurve = "linear" | "exponential";
+
+export type TempoRamp = {
+  bpm: number;
+  length: number;
+  unit: RampUnit;
+  curve: RampCurve;
+  at_bar: number | null;
+};
+
+export type ClockSource =
+  | { type: "internal" }
+  | { type: "osc"; path: string; ppqn: number }
+  | { type: "midi"; path: string };
+
+export type Metronome = {
+  enabled: boolean;
+  accent: MinOscMessage;
+  beat: MinOscMessage;
+  count_in: number;
+};
+
+export type MessageSource =
+  | { kind: "pattern"; name: string }
+  | { kind: "track"; name: string }
+  | { kind: "event"; name: string }
+  | { kind: "slider"; name: string }
+  | { kind: "metronome" };
+
+export type DeliveryOutcome = "sent" | "queued" | "dropped";
+
+export type MonitorEntry = {
+  time_ms: number;
+  tick: number | null;
+  source: MessageSource;
+  target: string;
+  msg: MinOscMessage;
+  outcome: DeliveryOutcome;
+};
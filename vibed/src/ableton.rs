@@ -0,0 +1,828 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    io,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    sync::Arc,
+    time::Duration,
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::{
+    net::UdpSocket,
+    select,
+    sync::{RwLock as AsyncRwLock, broadcast, mpsc},
+    time::{Instant, Interval, interval, sleep_until},
+};
+use tracing::{info, warn};
+
+use crate::{
+    MAX_BPM, MIN_BPM,
+    command::{ClientCommand, Severity},
+    ticker::TickerCommand,
+};
+
+const MULTICAST_GROUP: Ipv4Addr = Ipv4Addr::new(224, 76, 78, 75);
+const MULTICAST_PORT: u16 = 20808;
+const DISCOVERY_HEADER: &[u8; 8] = b"_asdp_v\x01";
+const MEASUREMENT_HEADER: &[u8; 8] = b"_link_v\x01";
+const MAX_MESSAGE_SIZE: usize = 512;
+const PEER_TTL_SECS: u8 = 5;
+const ALIVE_INTERVAL: Duration = Duration::from_millis(250);
+const MEASUREMENT_POINTS: usize = 100;
+const MEASUREMENT_TIMEOUT: Duration = Duration::from_millis(50); // per ping
+const MEASUREMENT_ATTEMPTS: usize = 5;
+const REMEASURE_AFTER: Duration = Duration::from_secs(30); // sessions we didn't join
+const SESSION_EPSILON_US: i64 = 500_000;
+
+// discovery message types
+const ALIVE: u8 = 1;
+const RESPONSE: u8 = 2;
+const BYEBYE: u8 = 3;
+// measurement message types
+const PING: u8 = 1;
+const PONG: u8 = 2;
+
+const KEY_TIMELINE: [u8; 4] = *b"tmln";
+const KEY_SESSION: [u8; 4] = *b"sess";
+const KEY_START_STOP: [u8; 4] = *b"stst";
+const KEY_ENDPOINT_V4: [u8; 4] = *b"mep4";
+const KEY_HOST_TIME: [u8; 4] = *b"__ht";
+const KEY_GHOST_TIME: [u8; 4] = *b"__gt";
+const KEY_PREV_GHOST_TIME: [u8; 4] = *b"_pgt";
+
+type NodeId = [u8; 8];
+
+#[derive(Debug)]
+pub enum AbletonCommand {
+    Enable {
+        enabled: bool,
+    },
+    /// Local tempo change, `anchor` being the ticker's next tick if playing
+    Tempo {
+        bpm: f32,
+        anchor: Option<Instant>,
+    },
+    Playing {
+        playing: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct AbletonState {
+    pub enabled: Arc<AsyncRwLock<bool>>,
+    pub peers: Arc<AsyncRwLock<usize>>,
+}
+
+#[derive(Debug)]
+pub struct AbletonArg {
+    pub cmd_rx: mpsc::Receiver<AbletonCommand>,
+    pub ticker_cmd_tx: mpsc::Sender<TickerCommand>,
+    pub client_cmd_broadcast_tx: broadcast::Sender<ClientCommand>,
+    pub bpm: Arc<AsyncRwLock<f32>>,
+    pub playing: Arc<AsyncRwLock<bool>>,
+}
+
+// LYN: Ableton Link
+
+pub async fn main(state: AbletonState, mut arg: AbletonArg) {
+    info!("Ableton Link started");
+
+    loop {
+        if !*state.enabled.read().await {
+            // local changes mean nothing outside a session
+            match arg.cmd_rx.recv().await {
+                Some(AbletonCommand::Enable { enabled: true }) => {
+                    *state.enabled.write().await = true;
+                }
+                Some(AbletonCommand::Enable { enabled: false }) => {
+                    broadcast(
+                        &arg.client_cmd_broadcast_tx,
+                        ClientCommand::TickerLinkUpdated {
+                            enabled: false,
+                            peers: 0,
+                        },
+                    );
+                    continue;
+                }
+                Some(_) => continue,
+                None => return,
+            }
+        }
+
+        let mut session = match Session::found(&arg).await {
+            Ok(session) => session,
+            Err(err) => {
+                warn!("Failed to start Ableton Link: {}", err);
+                *state.enabled.write().await = false;
+                broadcast(
+                    &arg.client_cmd_broadcast_tx,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Start Ableton Link".to_string(),
+                        detail: err.to_string(),
+                    },
+                );
+                broadcast(
+                    &arg.client_cmd_broadcast_tx,
+                    ClientCommand::TickerLinkUpdated {
+                        enabled: false,
+                        peers: 0,
+                    },
+                );
+                continue;
+            }
+        };
+        info!("Ableton Link session founded on {}", session.interface);
+        broadcast(
+            &arg.client_cmd_broadcast_tx,
+            ClientCommand::TickerLinkUpdated {
+                enabled: true,
+                peers: 0,
+            },
+        );
+        // the ticker lays its own grid on the session, its tempo staying as it is
+        let bpm = *arg.bpm.read().await;
+        arg.ticker_cmd_tx
+            .send(TickerCommand::SetBPM { bpm })
+            .await
+            .expect("Ticker panicked!");
+
+        if let Err(err) = session.run(&state, &mut arg).await {
+            warn!("Ableton Link stopped: {}", err);
+        }
+        *state.enabled.write().await = false;
+        session.leave().await;
+        *state.peers.write().await = 0;
+        broadcast(
+            &arg.client_cmd_broadcast_tx,
+            ClientCommand::TickerLinkUpdated {
+                enabled: false,
+                peers: 0,
+            },
+        );
+    }
+}
+
+// LYN: Timeline
+
+/// The session's beat grid, `tempo` in microseconds per beat, beats in millionths
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Timeline {
+    tempo: i64,
+    beat_origin: i64,
+    time_origin: i64, // ghost time
+}
+
+impl Timeline {
+    fn new(bpm: f32, beat: f64, ghost: i64) -> Self {
+        Self {
+            tempo: (60_000_000.0 / bpm.clamp(MIN_BPM, MAX_BPM) as f64).round() as i64,
+            beat_origin: (beat * 1e6).round() as i64,
+            time_origin: ghost,
+        }
+    }
+
+    fn bpm(&self) -> f32 {
+        (60_000_000.0 / self.tempo as f64) as f32
+    }
+
+    fn beat_at(&self, ghost: i64) -> f64 {
+        (self.beat_origin as f64 + (ghost - self.time_origin) as f64 * 1e6 / self.tempo as f64)
+            / 1e6
+    }
+
+    /// Whether a peer's timeline takes over ours, later origins winning and slower tempos on
+    /// a tie, the way every Link peer decides
+    fn superseded_by(&self, other: &Timeline) -> bool {
+        other.beat_origin > self.beat_origin
+            || (other.beat_origin == self.beat_origin && other.tempo > self.tempo)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        [self.tempo, self.beat_origin, self.time_origin]
+            .iter()
+            .flat_map(|val| val.to_be_bytes())
+            .collect()
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        Some(Self {
+            tempo: read_i64(buf, 0)?,
+            beat_origin: read_i64(buf, 8)?,
+            time_origin: read_i64(buf, 16)?,
+        })
+        .filter(|timeline| timeline.tempo > 0)
+        // a peer's tempo is held to the range we'd send ourselves
+        .map(|timeline| Self {
+            tempo: timeline.tempo.clamp(
+                (60_000_000.0 / MAX_BPM as f64).round() as i64,
+                (60_000_000.0 / MIN_BPM as f64).round() as i64,
+            ),
+            ..timeline
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StartStop {
+    playing: bool,
+    beats: i64,
+    timestamp: i64, // ghost time, the latest change wins
+}
+
+impl StartStop {
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![self.playing as u8];
+        buf.extend_from_slice(&self.beats.to_be_bytes());
+        buf.extend_from_slice(&self.timestamp.to_be_bytes());
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        Some(Self {
+            playing: *buf.first()? != 0,
+            beats: read_i64(buf, 1)?,
+            timestamp: read_i64(buf, 9)?,
+        })
+    }
+}
+
+// LYN: Payload
+
+/// Key-size-value entries following the message header, unknown keys are skipped
+#[derive(Debug, Default)]
+struct Payload<'a> {
+    entries: Vec<([u8; 4], &'a [u8])>,
+}
+
+impl<'a> Payload<'a> {
+    fn parse(mut buf: &'a [u8]) -> Option<Self> {
+        let mut entries = Vec::new();
+        while !buf.is_empty() {
+            let key: [u8; 4] = buf.get(..4)?.try_into().ok()?;
+            let size = u32::from_be_bytes(buf.get(4..8)?.try_into().ok()?) as usize;
+            entries.push((key, buf.get(8..8 + size)?));
+            buf = &buf[8 + size..];
+        }
+        Some(Self { entries })
+    }
+
+    fn get(&self, key: [u8; 4]) -> Option<&'a [u8]> {
+        self.entries
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| *value)
+    }
+
+    fn time(&self, key: [u8; 4]) -> i64 {
+        self.get(key)
+            .and_then(|value| read_i64(value, 0))
+            .unwrap_or_default()
+    }
+}
+
+fn push_entry(buf: &mut Vec<u8>, key: [u8; 4], value: &[u8]) {
+    buf.extend_from_slice(&key);
+    buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+fn read_i64(buf: &[u8], at: usize) -> Option<i64> {
+    Some(i64::from_be_bytes(buf.get(at..at + 8)?.try_into().ok()?))
+}
+
+// LYN: Session
+
+#[derive(Debug)]
+struct Peer {
+    session: NodeId,
+    timeline: Timeline,
+    start_stop: Option<StartStop>,
+    endpoint: Option<SocketAddr>, // where it answers measurement pings
+    expires: Instant,
+}
+
+/// Clock offset measurement against another session, through one of its peers
+#[derive(Debug)]
+struct Measurement {
+    session: NodeId,
+    endpoint: SocketAddr,
+    samples: Vec<f64>, // ghost minus host time
+    deadline: Instant,
+    attempts: usize,
+    ping: Vec<u8>,
+}
+
+struct Session {
+    node: NodeId,
+    session: NodeId,
+    interface: Ipv4Addr,
+    epoch: Instant,
+    ghost_offset: i64, // ghost time minus host time
+    timeline: Timeline,
+    start_stop: StartStop,
+    peers: HashMap<NodeId, Peer>,
+    measurement: Option<Measurement>,
+    measured: HashMap<NodeId, Instant>, // sessions that lost against ours
+    multicast: UdpSocket,               // listens on the group
+    unicast: UdpSocket,                 // sends alive messages, gets responses
+    pinger: UdpSocket,                  // measurement endpoint
+    alive: Interval,
+    multicast_buf: Vec<u8>,
+    unicast_buf: Vec<u8>,
+    pinger_buf: Vec<u8>,
+}
+
+impl Session {
+    /// A session of our own, peers joining it or us theirs once measured
+    async fn found(arg: &AbletonArg) -> io::Result<Self> {
+        let interface = multicast_interface().await?;
+        let multicast = bind_multicast(interface)?;
+        let unicast = bind_unicast(interface)?;
+        let pinger = UdpSocket::bind((interface, 0)).await?;
+
+        let hash = RandomState::new().hash_one(Instant::now());
+        let node = hash.to_be_bytes();
+        let epoch = Instant::now();
+        let bpm = *arg.bpm.read().await;
+        let playing = *arg.playing.read().await;
+        Ok(Self {
+            node,
+            session: node,
+            interface,
+            epoch,
+            // ghost time starts at zero as the session is founded
+            ghost_offset: 0,
+            timeline: Timeline::new(bpm, 0.0, 0),
+            start_stop: StartStop {
+                playing,
+                beats: 0,
+                timestamp: 0,
+            },
+            peers: HashMap::new(),
+            measurement: None,
+            measured: HashMap::new(),
+            multicast,
+            unicast,
+            pinger,
+            alive: interval(ALIVE_INTERVAL),
+            multicast_buf: vec![0u8; MAX_MESSAGE_SIZE],
+            unicast_buf: vec![0u8; MAX_MESSAGE_SIZE],
+            pinger_buf: vec![0u8; MAX_MESSAGE_SIZE],
+        })
+    }
+
+    fn host_time(&self) -> i64 {
+        self.epoch.elapsed().as_micros() as i64
+    }
+
+    fn ghost_time(&self) -> i64 {
+        self.host_time() + self.ghost_offset
+    }
+
+    fn ghost_at(&self, at: Instant) -> i64 {
+        let host = if at >= self.epoch {
+            (at - self.epoch).as_micros() as i64
+        } else {
+            -((self.epoch - at).as_micros() as i64)
+        };
+        host + self.ghost_offset
+    }
+
+    async fn run(&mut self, state: &AbletonState, arg: &mut AbletonArg) -> io::Result<()> {
+        loop {
+            let deadline = self.measurement.as_ref().map(|m| m.deadline);
+            select! {
+                res = self.multicast.recv_from(&mut self.multicast_buf) => {
+                    let (len, from) = res?;
+                    let msg = self.multicast_buf[..len].to_vec();
+                    self.handle_discovery(&msg, from, state, arg).await?;
+                }
+                res = self.unicast.recv_from(&mut self.unicast_buf) => {
+                    let (len, from) = res?;
+                    let msg = self.unicast_buf[..len].to_vec();
+                    self.handle_discovery(&msg, from, state, arg).await?;
+                }
+                res = self.pinger.recv_from(&mut self.pinger_buf) => {
+                    let (len, from) = res?;
+                    let msg = self.pinger_buf[..len].to_vec();
+                    self.handle_measurement(&msg, from, arg).await?;
+                }
+                _ = self.alive.tick() => {
+                    self.send_discovery(ALIVE, None).await?;
+                    let now = Instant::now();
+                    let before = self.peers.len();
+                    self.peers.retain(|_, peer| peer.expires > now);
+                    if self.peers.len() != before {
+                        self.report_peers(state, arg).await;
+                    }
+                }
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    self.retry_measurement().await?;
+                }
+                Some(cmd) = arg.cmd_rx.recv() => {
+                    match cmd {
+                        AbletonCommand::Enable { enabled: true } => {}
+                        AbletonCommand::Enable { enabled: false } => return Ok(()),
+                        AbletonCommand::Tempo { bpm, anchor } => {
+                            if anchor.is_none() && (bpm - self.timeline.bpm()).abs() < 0.001 {
+                                continue;
+                            }
+                            // NOTE: the timeline starts now, peers only take timelines starting
+                            // later than the one they have, and the session's beat carries on
+                            // unchanged from the ticker's next tick
+                            let now = self.ghost_time();
+                            let beat = match anchor {
+                                Some(at) => {
+                                    let at = self.ghost_at(at);
+                                    self.timeline.beat_at(at) - (at - now) as f64 * bpm as f64 / 60e6
+                                }
+                                None => self.timeline.beat_at(now),
+                            };
+                            self.timeline = Timeline::new(bpm, beat, now);
+                            self.send_discovery(ALIVE, None).await?;
+                        }
+                        AbletonCommand::Playing { playing } => {
+                            if playing != self.start_stop.playing {
+                                let now = self.ghost_time();
+                                self.start_stop = StartStop {
+                                    playing,
+                                    beats: (self.timeline.beat_at(now) * 1e6) as i64,
+                                    timestamp: now,
+                                };
+                                self.send_discovery(ALIVE, None).await?;
+                            }
+                            // started locally, on the session's next bar line
+                            if playing {
+                                self.follow(arg).await;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn leave(&mut self) {
+        if let Err(err) = self.send_discovery(BYEBYE, None).await {
+            warn!("Failed to leave Ableton Link session: {}", err);
+        }
+    }
+
+    // LYN: Discovery
+
+    async fn send_discovery(&self, kind: u8, to: Option<SocketAddr>) -> io::Result<()> {
+        let mut msg = DISCOVERY_HEADER.to_vec();
+        msg.push(kind);
+        msg.push(if kind == BYEBYE { 0 } else { PEER_TTL_SECS });
+        msg.extend_from_slice(&0u16.to_be_bytes()); // group
+        msg.extend_from_slice(&self.node);
+        if kind != BYEBYE {
+            push_entry(&mut msg, KEY_TIMELINE, &self.timeline.encode());
+            push_entry(&mut msg, KEY_SESSION, &self.session);
+            push_entry(&mut msg, KEY_START_STOP, &self.start_stop.encode());
+            let endpoint = self.pinger.local_addr()?;
+            let mut value = self.interface.octets().to_vec();
+            value.extend_from_slice(&endpoint.port().to_be_bytes());
+            push_entry(&mut msg, KEY_ENDPOINT_V4, &value);
+        }
+        let to = to.unwrap_or(SocketAddr::V4(SocketAddrV4::new(
+            MULTICAST_GROUP,
+            MULTICAST_PORT,
+        )));
+        self.unicast.send_to(&msg, to).await.map(|_| ())
+    }
+
+    async fn handle_discovery(
+        &mut self,
+        msg: &[u8],
+        from: SocketAddr,
+        state: &AbletonState,
+        arg: &AbletonArg,
+    ) -> io::Result<()> {
+        let Some(rest) = msg.strip_prefix(DISCOVERY_HEADER) else {
+            return Ok(());
+        };
+        let Some(&[kind, ttl, _, _, ref ident @ ..]) = rest.get(..12) else {
+            return Ok(());
+        };
+        let ident: NodeId = ident.try_into().unwrap();
+        if ident == self.node {
+            return Ok(()); // our own, looped back
+        }
+        match kind {
+            BYEBYE if self.peers.remove(&ident).is_some() => {
+                self.report_peers(state, arg).await;
+            }
+            BYEBYE => {}
+            ALIVE | RESPONSE => {
+                if kind == ALIVE {
+                    self.send_discovery(RESPONSE, Some(from)).await?;
+                }
+                let Some(payload) = Payload::parse(&rest[12..]) else {
+                    return Ok(());
+                };
+                let (Some(timeline), Some(session)) = (
+                    payload.get(KEY_TIMELINE).and_then(Timeline::decode),
+                    payload
+                        .get(KEY_SESSION)
+                        .and_then(|value| NodeId::try_from(value).ok()),
+                ) else {
+                    return Ok(());
+                };
+                let endpoint = payload.get(KEY_ENDPOINT_V4).and_then(|value| {
+                    let ip: [u8; 4] = value.get(..4)?.try_into().ok()?;
+                    let port = u16::from_be_bytes(value.get(4..6)?.try_into().ok()?);
+                    Some(SocketAddr::V4(SocketAddrV4::new(ip.into(), port)))
+                });
+                let start_stop = payload.get(KEY_START_STOP).and_then(StartStop::decode);
+                let known = self
+                    .peers
+                    .insert(
+                        ident,
+                        Peer {
+                            session,
+                            timeline,
+                            start_stop,
+                            endpoint,
+                            expires: Instant::now() + Duration::from_secs(ttl as u64),
+                        },
+                    )
+                    .is_some();
+                if !known {
+                    self.report_peers(state, arg).await;
+                }
+                self.saw_peer(ident, arg).await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn saw_peer(&mut self, ident: NodeId, arg: &AbletonArg) -> io::Result<()> {
+        let peer = &self.peers[&ident];
+        if peer.session != self.session {
+            let recently = self
+                .measured
+                .get(&peer.session)
+                .is_some_and(|at| at.elapsed() < REMEASURE_AFTER);
+            if self.measurement.is_none()
+                && !recently
+                && let Some(endpoint) = peer.endpoint
+            {
+                self.start_measurement(peer.session, endpoint).await?;
+            }
+            return Ok(());
+        }
+
+        let (timeline, start_stop) = (peer.timeline, peer.start_stop);
+        let mut changed = false;
+        if self.timeline.superseded_by(&timeline) {
+            self.timeline = timeline;
+            changed = true;
+        }
+        if let Some(start_stop) = start_stop
+            && start_stop.timestamp > self.start_stop.timestamp
+        {
+            changed |= start_stop.playing != self.start_stop.playing;
+            self.start_stop = start_stop;
+        }
+        if changed {
+            self.follow(arg).await;
+        }
+        Ok(())
+    }
+
+    /// Brings the ticker onto the session's tempo, phase and transport
+    async fn follow(&self, arg: &AbletonArg) {
+        let playing = *arg.playing.read().await;
+        if self.start_stop.playing != playing {
+            let (cmd, client_cmd) = if self.start_stop.playing {
+                (TickerCommand::Play, ClientCommand::TickerPlaying)
+            } else {
+                // NOTE: a stop from the session pauses, tracks keep where they were
+                (TickerCommand::Pause, ClientCommand::TickerPaused)
+            };
+            arg.ticker_cmd_tx.send(cmd).await.expect("Ticker panicked!");
+            broadcast(&arg.client_cmd_broadcast_tx, client_cmd);
+        }
+        arg.ticker_cmd_tx
+            .send(TickerCommand::Follow {
+                bpm: self.timeline.bpm(),
+                beat: self.timeline.beat_at(self.ghost_time()),
+                at: Instant::now(),
+            })
+            .await
+            .expect("Ticker panicked!");
+    }
+
+    async fn report_peers(&self, state: &AbletonState, arg: &AbletonArg) {
+        let peers = self.peers.len();
+        *state.peers.write().await = peers;
+        info!("Ableton Link session has {} peers", peers);
+        broadcast(
+            &arg.client_cmd_broadcast_tx,
+            ClientCommand::TickerLinkUpdated {
+                enabled: true,
+                peers,
+            },
+        );
+    }
+
+    // LYN: Measurement
+
+    fn ping(&self, prev_ghost: Option<i64>) -> Vec<u8> {
+        let mut msg = MEASUREMENT_HEADER.to_vec();
+        msg.push(PING);
+        push_entry(&mut msg, KEY_HOST_TIME, &self.host_time().to_be_bytes());
+        if let Some(prev_ghost) = prev_ghost {
+            push_entry(&mut msg, KEY_PREV_GHOST_TIME, &prev_ghost.to_be_bytes());
+        }
+        msg
+    }
+
+    async fn start_measurement(&mut self, session: NodeId, endpoint: SocketAddr) -> io::Result<()> {
+        let ping = self.ping(None);
+        self.pinger.send_to(&ping, endpoint).await?;
+        self.measurement = Some(Measurement {
+            session,
+            endpoint,
+            samples: Vec::with_capacity(MEASUREMENT_POINTS + 2),
+            deadline: Instant::now() + MEASUREMENT_TIMEOUT,
+            attempts: 1,
+            ping,
+        });
+        Ok(())
+    }
+
+    async fn retry_measurement(&mut self) -> io::Result<()> {
+        let Some(measurement) = &mut self.measurement else {
+            return Ok(());
+        };
+        if measurement.attempts >= MEASUREMENT_ATTEMPTS {
+            warn!(
+                "Ableton Link peer at {} stopped answering",
+                measurement.endpoint
+            );
+            self.measured.insert(measurement.session, Instant::now());
+            self.measurement = None;
+            return Ok(());
+        }
+        measurement.attempts += 1;
+        measurement.deadline = Instant::now() + MEASUREMENT_TIMEOUT;
+        self.pinger
+            .send_to(&measurement.ping, measurement.endpoint)
+            .await
+            .map(|_| ())
+    }
+
+    async fn handle_measurement(
+        &mut self,
+        msg: &[u8],
+        from: SocketAddr,
+        arg: &AbletonArg,
+    ) -> io::Result<()> {
+        let Some(rest) = msg.strip_prefix(MEASUREMENT_HEADER) else {
+            return Ok(());
+        };
+        let Some((&kind, rest)) = rest.split_first() else {
+            return Ok(());
+        };
+        match kind {
+            PING => {
+                // our session and ghost time, then the ping's own payload back
+                let mut pong = MEASUREMENT_HEADER.to_vec();
+                pong.push(PONG);
+                push_entry(&mut pong, KEY_SESSION, &self.session);
+                push_entry(&mut pong, KEY_GHOST_TIME, &self.ghost_time().to_be_bytes());
+                pong.extend_from_slice(rest);
+                self.pinger.send_to(&pong, from).await?;
+            }
+            PONG => {
+                let host_now = self.host_time();
+                let Some(measurement) = &mut self.measurement else {
+                    return Ok(());
+                };
+                let Some(payload) = Payload::parse(rest) else {
+                    return Ok(());
+                };
+                if from != measurement.endpoint
+                    || payload.get(KEY_SESSION) != Some(&measurement.session[..])
+                {
+                    return Ok(());
+                }
+                let ghost = payload.time(KEY_GHOST_TIME);
+                let prev_ghost = payload.time(KEY_PREV_GHOST_TIME);
+                let host = payload.time(KEY_HOST_TIME);
+                if ghost != 0 && host != 0 {
+                    measurement
+                        .samples
+                        .push(ghost as f64 - (host_now + host) as f64 / 2.0);
+                    if prev_ghost != 0 {
+                        measurement
+                            .samples
+                            .push((ghost + prev_ghost) as f64 / 2.0 - host as f64);
+                    }
+                }
+                if measurement.samples.len() > MEASUREMENT_POINTS {
+                    let Measurement {
+                        session,
+                        mut samples,
+                        ..
+                    } = self.measurement.take().unwrap();
+                    samples.sort_by(f64::total_cmp);
+                    let offset = samples[samples.len() / 2].round() as i64;
+                    self.measured_session(session, offset, arg).await?;
+                } else {
+                    let ping = self.ping(Some(ghost));
+                    let measurement = self.measurement.as_mut().unwrap();
+                    measurement.ping = ping;
+                    measurement.attempts = 1;
+                    measurement.deadline = Instant::now() + MEASUREMENT_TIMEOUT;
+                    self.pinger.send_to(&measurement.ping, from).await?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Joins the other session if it has been around longer, ghost times being the age of a
+    /// session, the lower id winning when they're about as old
+    async fn measured_session(
+        &mut self,
+        session: NodeId,
+        offset: i64,
+        arg: &AbletonArg,
+    ) -> io::Result<()> {
+        let host = self.host_time();
+        let diff = (host + offset) - (host + self.ghost_offset);
+        if diff > SESSION_EPSILON_US || (diff.abs() < SESSION_EPSILON_US && session < self.session)
+        {
+            info!("Joining Ableton Link session {:02x?}", session);
+            self.measured.insert(self.session, Instant::now());
+            self.session = session;
+            self.ghost_offset = offset;
+            let peers = self.peers.values().filter(|peer| peer.session == session);
+            if let Some(timeline) = peers
+                .clone()
+                .map(|peer| peer.timeline)
+                .max_by_key(|timeline| (timeline.beat_origin, timeline.tempo))
+            {
+                self.timeline = timeline;
+            }
+            if let Some(start_stop) = peers
+                .filter_map(|peer| peer.start_stop)
+                .max_by_key(|start_stop| start_stop.timestamp)
+            {
+                self.start_stop = start_stop;
+            }
+            self.send_discovery(ALIVE, None).await?;
+            self.follow(arg).await;
+        } else {
+            self.measured.insert(session, Instant::now());
+        }
+        Ok(())
+    }
+}
+
+// LYN: Sockets
+
+/// Address of the interface multicast goes out of, the one the default route uses
+async fn multicast_interface() -> io::Result<Ipv4Addr> {
+    let probe = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    probe.connect((MULTICAST_GROUP, MULTICAST_PORT)).await?;
+    match probe.local_addr()? {
+        SocketAddr::V4(addr) => Ok(*addr.ip()),
+        SocketAddr::V6(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "no IPv4 interface for multicast",
+        )),
+    }
+}
+
+/// Shared with every other Link peer on this machine
+fn bind_multicast(interface: Ipv4Addr) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, MULTICAST_PORT).into())?;
+    socket.join_multicast_v4(&MULTICAST_GROUP, &interface)?;
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+fn bind_unicast(interface: Ipv4Addr) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.bind(&SocketAddrV4::new(interface, 0).into())?;
+    socket.set_multicast_if_v4(&interface)?;
+    socket.set_multicast_loop_v4(true)?; // peers on this machine
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+fn broadcast(client_cmd_broadcast_tx: &broadcast::Sender<ClientCommand>, cmd: ClientCommand) {
+    if let Err(err) = client_cmd_broadcast_tx.send(cmd) {
+        warn!("Failed to broadcast client command: {}", err);
+    };
+}
@@ -0,0 +1,234 @@
+use std::{collections::BTreeMap, fmt, io, sync::Arc, time::Duration};
+
+use futures::future::BoxFuture;
+use rosc::{OscPacket, OscType};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    net::UdpSocket,
+    select,
+    sync::RwLock as AsyncRwLock,
+    time::{Interval, interval},
+};
+
+use crate::{
+    mosc::{MinOscMessage, flatten_packet},
+    sink::{OutputSink, SinkConnector, connect_udp, with_default_port},
+};
+
+const ARTNET_PORT: u16 = 6454;
+const ARTNET_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+const ARTNET_HEADER: &[u8; 8] = b"Art-Net\0";
+const ARTNET_OP_DMX: u16 = 0x5000;
+const ARTNET_PROTOCOL_VERSION: u16 = 14;
+const DMX_CHANNELS: usize = 512;
+const MAX_UNIVERSE: u16 = 0x7fff; // 15-bit port address
+
+// LYN: Mapping
+
+/// Where the numeric args of messages on `path` go, the first one to `channel` and each
+/// further one to the channel after
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DmxMapping {
+    pub path: String,
+    pub universe: u16,
+    pub channel: u16, // 1-512
+    #[serde(default)]
+    pub min: f32, // value for level 0
+    #[serde(default = "DmxMapping::default_max")]
+    pub max: f32, // value for level 255
+}
+
+#[derive(Debug)]
+pub enum DmxMappingError {
+    PathNotAbsolute(String),
+    UniverseOutOfRange(u16),
+    ChannelOutOfRange(u16),
+    EmptyRange { min: f32, max: f32 },
+}
+
+impl fmt::Display for DmxMappingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DmxMappingError::PathNotAbsolute(path) => {
+                write!(f, "Path \"{}\" must start with \"/\"", path)
+            }
+            DmxMappingError::UniverseOutOfRange(universe) => write!(
+                f,
+                "Universe {} is out of range, expecting 0 to {}",
+                universe, MAX_UNIVERSE
+            ),
+            DmxMappingError::ChannelOutOfRange(channel) => write!(
+                f,
+                "Channel {} is out of range, expecting 1 to {}",
+                channel, DMX_CHANNELS
+            ),
+            DmxMappingError::EmptyRange { min, max } => {
+                write!(f, "Range {} to {} is empty or not finite", min, max)
+            }
+        }
+    }
+}
+
+impl DmxMapping {
+    fn default_max() -> f32 {
+        1.0
+    }
+
+    pub fn validate(&self) -> Result<(), DmxMappingError> {
+        if !self.path.starts_with('/') {
+            return Err(DmxMappingError::PathNotAbsolute(self.path.clone()));
+        }
+        if self.universe > MAX_UNIVERSE {
+            return Err(DmxMappingError::UniverseOutOfRange(self.universe));
+        }
+        if self.channel == 0 || self.channel as usize > DMX_CHANNELS {
+            return Err(DmxMappingError::ChannelOutOfRange(self.channel));
+        }
+        if !self.min.is_finite() || !self.max.is_finite() || self.min == self.max {
+            return Err(DmxMappingError::EmptyRange {
+                min: self.min,
+                max: self.max,
+            });
+        }
+        Ok(())
+    }
+
+    fn level(&self, val: f32) -> u8 {
+        let ratio = ((val - self.min) / (self.max - self.min)).clamp(0.0, 1.0);
+        (ratio * 255.0).round() as u8
+    }
+}
+
+// LYN: Sink
+
+/// Art-Net DMX frames over UDP, `artnet://<host>[:port]`, levels set by the project's mappings
+pub struct ArtNetConnector {
+    pub mappings: Arc<AsyncRwLock<Vec<DmxMapping>>>,
+}
+
+impl SinkConnector for ArtNetConnector {
+    fn connect(&self, addr: String) -> BoxFuture<'static, io::Result<Box<dyn OutputSink>>> {
+        let mappings = self.mappings.clone();
+        Box::pin(async move {
+            let addr = with_default_port(&addr, ARTNET_PORT);
+            Ok(Box::new(ArtNetSink {
+                socket: connect_udp(&addr).await?,
+                mappings,
+                universes: BTreeMap::new(),
+                refresh: interval(ARTNET_REFRESH_INTERVAL),
+                buf: vec![0u8; DMX_CHANNELS],
+            }) as Box<dyn OutputSink>)
+        })
+    }
+}
+
+struct Universe {
+    levels: [u8; DMX_CHANNELS],
+    sequence: u8,
+}
+
+impl Universe {
+    fn new() -> Self {
+        Self {
+            levels: [0; DMX_CHANNELS],
+            sequence: 0,
+        }
+    }
+
+    /// An ArtDmx packet with all 512 channels
+    fn frame(&mut self, universe: u16) -> Vec<u8> {
+        // 0 would disable sequencing on the receiver
+        self.sequence = self.sequence.checked_add(1).unwrap_or(1);
+        let mut frame = Vec::with_capacity(18 + DMX_CHANNELS);
+        frame.extend_from_slice(ARTNET_HEADER);
+        frame.extend_from_slice(&ARTNET_OP_DMX.to_le_bytes());
+        frame.extend_from_slice(&ARTNET_PROTOCOL_VERSION.to_be_bytes());
+        frame.push(self.sequence);
+        frame.push(0); // physical input port, informational
+        frame.extend_from_slice(&universe.to_le_bytes()); // sub-net/universe, then net
+        frame.extend_from_slice(&(DMX_CHANNELS as u16).to_be_bytes());
+        frame.extend_from_slice(&self.levels);
+        frame
+    }
+}
+
+struct ArtNetSink {
+    socket: UdpSocket,
+    mappings: Arc<AsyncRwLock<Vec<DmxMapping>>>,
+    universes: BTreeMap<u16, Universe>,
+    refresh: Interval,
+    buf: Vec<u8>,
+}
+
+impl OutputSink for ArtNetSink {
+    fn send<'a>(&'a mut self, packet: &'a OscPacket) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let mut touched = Vec::new();
+            {
+                let mappings = self.mappings.read().await;
+                for msg in flatten_packet(packet.clone()) {
+                    for mapping in mappings.iter().filter(|mapping| mapping.path == msg.addr) {
+                        let universe = self
+                            .universes
+                            .entry(mapping.universe)
+                            .or_insert_with(Universe::new);
+                        let first = mapping.channel as usize - 1;
+                        for (channel, arg) in (first..DMX_CHANNELS).zip(&msg.args) {
+                            if let Some(val) = as_f32(arg) {
+                                universe.levels[channel] = mapping.level(val);
+                            }
+                        }
+                        if !touched.contains(&mapping.universe) {
+                            touched.push(mapping.universe);
+                        }
+                    }
+                }
+            }
+            for universe in touched {
+                let frame = self.universes.get_mut(&universe).unwrap().frame(universe);
+                self.socket.send(&frame).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn recv(&mut self) -> BoxFuture<'_, io::Result<Vec<MinOscMessage>>> {
+        Box::pin(async move {
+            loop {
+                select! {
+                    // nothing for us in there (ArtPoll and such), but ICMP errors surface here
+                    res = self.socket.recv(&mut self.buf) => {
+                        res?;
+                    }
+                    // NOTE: receivers fall back to their own state when frames stop coming,
+                    // so the levels are sent again every second even if nothing changed
+                    _ = self.refresh.tick() => {
+                        for (universe, levels) in self.universes.iter_mut() {
+                            self.socket.send(&levels.frame(*universe)).await?;
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// DMX has no notion of time, levels change when the tick's messages arrive
+    fn timetagged(&self) -> bool {
+        false
+    }
+
+    fn connectionless(&self) -> bool {
+        true
+    }
+}
+
+fn as_f32(arg: &OscType) -> Option<f32> {
+    match arg {
+        OscType::Int(i) => Some(*i as f32),
+        OscType::Float(f) => Some(*f),
+        OscType::Long(l) => Some(*l as f32),
+        OscType::Double(d) => Some(*d as f32),
+        OscType::Bool(b) => Some(*b as u8 as f32),
+        _ => None,
+    }
+}
@@ -0,0 +1,332 @@
+use std::{collections::HashMap, io, net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{
+        ConnectInfo, Path, Query, State, WebSocketUpgrade,
+        ws::{Message, WebSocket},
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use futures::future::BoxFuture;
+use rosc::{OscPacket, decoder::decode_udp, encoder::encode};
+use serde::Deserialize;
+use tokio::{
+    select, spawn,
+    sync::{RwLock as AsyncRwLock, broadcast, watch},
+};
+use tracing::{info, warn};
+
+use crate::{
+    handler::HandlerState,
+    models::Target,
+    mosc::{MinOscMessage, flatten_packet},
+    sink::{OutputSink, SinkConnector, TargetAddr},
+};
+
+const BRIDGE_CHANNEL_CAPACITY: usize = 256;
+
+/// Browser subscribers of `ws://<channel>` targets, connected at `/osc/<channel>`
+#[derive(Debug, Clone, Default)]
+pub struct OscBridge {
+    channels: Arc<AsyncRwLock<HashMap<String, BridgeChannel>>>,
+}
+
+impl OscBridge {
+    /// Gets a channel, creating it for whichever side (target or subscriber) comes first
+    async fn channel(&self, name: &str) -> BridgeChannel {
+        if let Some(channel) = self.channels.read().await.get(name) {
+            return channel.clone();
+        }
+        self.channels
+            .write()
+            .await
+            .entry(name.to_string())
+            .or_insert_with(BridgeChannel::new)
+            .clone()
+    }
+
+    /// Drops a channel once neither a target nor a subscriber holds on to it anymore
+    async fn release(&self, name: &str, channel: BridgeChannel) {
+        drop(channel);
+        // NOTE: `channel` clones under the same lock, nobody can pick it up in between
+        let mut channels = self.channels.write().await;
+        if let Some(channel) = channels.get(name)
+            && Arc::strong_count(&channel.subscribers) == 1
+        {
+            channels.remove(name);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct BridgeChannel {
+    out_tx: broadcast::Sender<OscPacket>,
+    in_tx: broadcast::Sender<MinOscMessage>,
+    subscribers: Arc<watch::Sender<usize>>,
+}
+
+impl BridgeChannel {
+    fn new() -> Self {
+        Self {
+            out_tx: broadcast::channel(BRIDGE_CHANNEL_CAPACITY).0,
+            in_tx: broadcast::channel(BRIDGE_CHANNEL_CAPACITY).0,
+            subscribers: Arc::new(watch::channel(0).0),
+        }
+    }
+
+    /// Waits until at least one browser has subscribed
+    async fn subscribed(&self) {
+        // the sender is kept alive by `self`, so this can't fail
+        let _ = self.subscribers.subscribe().wait_for(|n| *n > 0).await;
+    }
+
+    fn send(&self, packet: &OscPacket) -> io::Result<()> {
+        match self.out_tx.send(packet.clone()) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "no bridge subscriber left",
+            )),
+        }
+    }
+
+    fn inbound(&self) -> BridgeInbound {
+        BridgeInbound {
+            in_rx: self.in_tx.subscribe(),
+            subscribers: self.subscribers.subscribe(),
+        }
+    }
+}
+
+/// Messages browsers send back, for as long as any of them stays subscribed
+#[derive(Debug)]
+struct BridgeInbound {
+    in_rx: broadcast::Receiver<MinOscMessage>,
+    subscribers: watch::Receiver<usize>,
+}
+
+impl BridgeInbound {
+    /// `None` once the last subscriber is gone
+    async fn recv(&mut self) -> Option<MinOscMessage> {
+        loop {
+            select! {
+                msg = self.in_rx.recv() => match msg {
+                    Ok(msg) => return Some(msg),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Dropped {} inbound bridge messages", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                },
+                _ = self.subscribers.wait_for(|n| *n == 0) => return None,
+            }
+        }
+    }
+}
+
+// LYN: Sink
+
+/// Browsers subscribed to the bridge channel named by the address
+pub struct BridgeConnector {
+    pub bridge: OscBridge,
+}
+
+impl SinkConnector for BridgeConnector {
+    fn connect(&self, addr: String) -> BoxFuture<'static, io::Result<Box<dyn OutputSink>>> {
+        let bridge = self.bridge.clone();
+        Box::pin(async move {
+            let channel = bridge.channel(&addr).await;
+            channel.subscribed().await;
+            Ok(Box::new(BridgeSink {
+                inbound: channel.inbound(),
+                channel: Some(channel),
+                bridge,
+                name: addr,
+            }) as Box<dyn OutputSink>)
+        })
+    }
+}
+
+struct BridgeSink {
+    channel: Option<BridgeChannel>, // only taken on drop
+    inbound: BridgeInbound,
+    bridge: OscBridge,
+    name: String,
+}
+
+impl Drop for BridgeSink {
+    fn drop(&mut self) {
+        if let Some(channel) = self.channel.take() {
+            let bridge = self.bridge.clone();
+            let name = std::mem::take(&mut self.name);
+            spawn(async move { bridge.release(&name, channel).await });
+        }
+    }
+}
+
+impl OutputSink for BridgeSink {
+    fn send<'a>(&'a mut self, packet: &'a OscPacket) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move { self.channel.as_ref().unwrap().send(packet) })
+    }
+
+    fn recv(&mut self) -> BoxFuture<'_, io::Result<Vec<MinOscMessage>>> {
+        Box::pin(async move {
+            match self.inbound.recv().await {
+                Some(msg) => Ok(vec![msg]),
+                None => Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "all bridge subscribers left",
+                )),
+            }
+        })
+    }
+}
+
+// LYN: Subscriber
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BridgeFormat {
+    /// Every message as a `MinOscMessage` in a text frame, bundles flattened
+    #[default]
+    Json,
+    /// Binary OSC packets, bundles and their timetags kept
+    Osc,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BridgeQuery {
+    #[serde(default)]
+    format: BridgeFormat,
+}
+
+pub async fn bridge_upgrader(
+    ws: WebSocketUpgrade,
+    Path(channel): Path<String>,
+    Query(BridgeQuery { format }): Query<BridgeQuery>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    state: State<HandlerState>,
+) -> Response {
+    let channel_name = channel;
+    let configured = state
+        .communicator_state
+        .targets
+        .read()
+        .await
+        .values()
+        .any(|target| names_channel(target, &channel_name));
+    if !configured {
+        warn!(
+            "Bridge subscriber {} asked for unknown channel {}",
+            addr, channel_name
+        );
+        return (
+            StatusCode::NOT_FOUND,
+            format!("No ws:// target names channel \"{}\"", channel_name),
+        )
+            .into_response();
+    }
+    let bridge = state.bridge.clone();
+    let channel = bridge.channel(&channel_name).await;
+    ws.on_upgrade(move |socket| async move {
+        info!("Bridge subscriber {} joined {}", addr, channel_name);
+        let channel = bridge_handler(socket, channel, format).await;
+        bridge.release(&channel_name, channel).await;
+        info!("Bridge subscriber {} left {}", addr, channel_name);
+    })
+    .into_response()
+}
+
+/// Whether the target sends to the channel, from its address or one of its fallbacks
+fn names_channel(target: &Target, channel: &str) -> bool {
+    std::iter::once(&target.addr)
+        .chain(&target.fallbacks)
+        .filter_map(|addr| addr.parse::<TargetAddr>().ok())
+        .any(|addr| addr.scheme == "ws" && addr.addr == channel)
+}
+
+/// Hands the channel back once the subscriber is gone
+async fn bridge_handler(
+    mut socket: WebSocket,
+    channel: BridgeChannel,
+    format: BridgeFormat,
+) -> BridgeChannel {
+    let mut out_rx = channel.out_tx.subscribe();
+    channel.subscribers.send_modify(|n| *n += 1);
+
+    loop {
+        select! {
+            packet = out_rx.recv() => {
+                let packet = match packet {
+                    Ok(packet) => packet,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Bridge subscriber lagged, skipped {} packets", n);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if let Err(err) = send_packet(&mut socket, packet, format).await {
+                    warn!("Failed to send to bridge subscriber: {}", err);
+                    break;
+                }
+            }
+            msg = socket.recv() => {
+                let Some(Ok(msg)) = msg else {
+                    break;
+                };
+                let msgs = match msg {
+                    Message::Text(msg) => match serde_json::from_str::<MinOscMessage>(&msg) {
+                        Ok(msg) => vec![msg],
+                        Err(err) => {
+                            warn!("Failed to parse bridge message {:?}: {}", msg, err);
+                            continue;
+                        }
+                    },
+                    Message::Binary(packet) => match decode_udp(&packet) {
+                        Ok((_, packet)) => flatten_packet(packet)
+                            .into_iter()
+                            .filter_map(|msg| MinOscMessage::try_from(msg).ok())
+                            .collect(),
+                        Err(err) => {
+                            warn!("Failed to decode bridge osc packet: {:?}", err);
+                            continue;
+                        }
+                    },
+                    Message::Close(_) => break,
+                    _ => continue,
+                };
+                for msg in msgs {
+                    // nobody listening just means the target isn't linked yet
+                    let _ = channel.in_tx.send(msg);
+                }
+            }
+        }
+    }
+
+    channel.subscribers.send_modify(|n| *n -= 1);
+    channel
+}
+
+async fn send_packet(
+    socket: &mut WebSocket,
+    packet: OscPacket,
+    format: BridgeFormat,
+) -> Result<(), axum::Error> {
+    match format {
+        BridgeFormat::Json => {
+            for msg in flatten_packet(packet) {
+                let Ok(msg) = MinOscMessage::try_from(msg) else {
+                    continue;
+                };
+                let msg = serde_json::to_string(&msg).expect("Failed to serialize message");
+                socket.send(Message::Text(msg.into())).await?;
+            }
+            Ok(())
+        }
+        BridgeFormat::Osc => {
+            // FIXME: handle invalid osc message error
+            let packet = encode(&packet).unwrap();
+            socket.send(Message::Binary(packet.into())).await
+        }
+    }
+}
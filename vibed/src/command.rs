@@ -2,7 +2,20 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-use crate::models::{Event, Pattern, Slider, Track};
+use crate::{
+    artnet::DmxMapping,
+    communicator::QueueStats,
+    models::{
+        DeliveryPolicies, DeliveryPolicy, Event, MessageKind, Metronome, Pattern, Route, Slider,
+        Target, TimeSignature, Track,
+    },
+    monitor::MonitorEntry,
+    mosc::MinOscMessage,
+    rtpmidi::MidiMapping,
+    sink::SinkOptions,
+    ticker::{ClockSource, TempoRamp, TickLateness},
+    transform::TransformRule,
+};
 
 #[rustfmt::skip]
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +24,21 @@ pub enum ServerCommand {
     SetProjectName { name: String },
 
     CommChangeAddr { addr: String },
+    CommTargetAdd { name: String, addr: String },
+    CommTargetDelete { name: String },
+    CommTargetChangeAddr { name: String, addr: String },
+    CommTargetSetTiming { name: String, lookahead_ms: u64, latency_ms: u64 },
+    CommTargetSetFallbacks { name: String, fallbacks: Vec<String>, grace_ms: u64 },
+    CommTargetSetEcho { name: String, echo_path: Option<String>, auto_latency: bool },
+    CommTargetSetTransforms { name: String, transforms: Vec<TransformRule> },
+    CommRouteAdd { route: Route },
+    CommRouteDelete { route: Route },
+    CommSetDelivery { kind: MessageKind, policy: DeliveryPolicy },
+    CommSetDmxMappings { mappings: Vec<DmxMapping> },
+    CommSetMidiMappings { mappings: Vec<MidiMapping> },
+    CommSetSinkOptions { scheme: String, options: SinkOptions },
+    CommMonitorSubscribe { prefix: Option<String> },
+    CommMonitorUnsubscribe,
 
     CtrlChangeContext { context: Option<String> },
 
@@ -33,23 +61,46 @@ pub enum ServerCommand {
     SliderDelete { name: String },
     SliderEdit { name: String, slider: Slider },
     SliderSetVal { name: String, val: f32 },
+    SliderSetRate { rate_hz: f32 },
 
     TickerPlay,
     TickerPause,
     TickerStop,
     TickerSetBpm { bpm: f32 },
+    TickerSetResolution { resolution: usize },
+    TickerSetTimeSignature { time_signature: TimeSignature },
+    TickerRamp { ramp: TempoRamp },
+    TickerTap,
+    TickerNudge { ms: i32 },
+    TickerSetLink { enabled: bool },
+    TickerSetClock { clock: ClockSource },
+    TickerSetMetronome { metronome: Metronome },
 
     RequestTickerBpm,
+    RequestTickerResolution,
+    RequestTickerTimeSignature,
     RequestTickerPlaying,
     RequestTickerTick,
+    RequestTickerLateness,
+    RequestTickerLink,
+    RequestTickerClock,
+    RequestTickerMetronome,
     RequestProjectName,
     RequestCommAddr,
     RequestCommStatus,
+    RequestCommTargets,
+    RequestCommRoutes,
+    RequestCommDelivery,
+    RequestCommQueues,
+    RequestCommDmxMappings,
+    RequestCommMidiMappings,
+    RequestCommSinkOptions,
     RequestCtrlContext,
     RequestAllTracks,
     RequestAllPatterns,
     RequestAllEvents,
     RequestAllSliders,
+    RequestSliderRate,
 }
 
 #[rustfmt::skip]
@@ -60,6 +111,25 @@ pub enum ClientCommand {
 
     CommAddrChanged { addr: String },
     CommStatusChanged { established: bool },
+    CommTargetAdded { name: String, target: Target },
+    CommTargetDeleted { name: String },
+    CommTargetAddrChanged { name: String, addr: String },
+    CommTargetStatusChanged { name: String, established: bool },
+    CommTargetTimingChanged { name: String, lookahead_ms: u64, latency_ms: u64 },
+    CommTargetFallbacksChanged { name: String, fallbacks: Vec<String>, grace_ms: u64 },
+    CommTargetActiveChanged { name: String, addr: String, primary: bool },
+    CommTargetEchoChanged { name: String, echo_path: Option<String>, auto_latency: bool },
+    CommTargetLatency { name: String, rtt_ms: f32, jitter_ms: f32 },
+    CommTargetTransformsChanged { name: String, transforms: Vec<TransformRule> },
+    CommRouteAdded { route: Route },
+    CommRouteDeleted { route: Route },
+    CommMessageReceived { target: String, msg: MinOscMessage },
+    CommDeliveryUpdated { kind: MessageKind, policy: DeliveryPolicy },
+    CommDmxMappingsUpdated { mappings: Vec<DmxMapping> },
+    CommMidiMappingsUpdated { mappings: Vec<MidiMapping> },
+    CommSinkOptionsUpdated { scheme: String, options: SinkOptions },
+    CommQueueUpdated { name: String, stats: QueueStats },
+    CommMonitorEntry { entry: MonitorEntry },
 
     CtrlContextChanged { context: Option<String> },
 
@@ -82,24 +152,49 @@ pub enum ClientCommand {
     SliderDeleted { name: String },
     SliderEdited { name: String, slider: Slider },
     SliderValSet { name: String, val: f32 },
+    SliderRateUpdated { rate_hz: f32 },
 
     TickerPlaying,
     TickerPaused,
     TickerStopped,
-    TickerTick { tick: usize, max: usize },
-    TickerBpmUpdated { bpm: f32 },
+    TickerTick { tick: usize, max: usize, bar: usize },
+    TickerBpmUpdated {
+        bpm: f32,
+        #[serde(default)]
+        time_signature: TimeSignature, // sent along so clients get both together
+    },
+    TickerRampProgress { bpm: f32, target_bpm: f32, progress: f32 },
+    TickerResolutionUpdated { resolution: usize },
+    TickerLinkUpdated { enabled: bool, peers: usize },
+    TickerClockUpdated { clock: ClockSource },
+    TickerMetronomeUpdated { metronome: Metronome },
+    TickerCountIn { bars_left: usize },
 
     ResponseTickerBpm { bpm: f32 },
+    ResponseTickerResolution { resolution: usize },
+    ResponseTickerTimeSignature { time_signature: TimeSignature },
     ResponseTickerPlaying { playing: bool },
     ResponseTickerTick { tick: isize, max: usize },
+    ResponseTickerLateness { lateness: TickLateness },
+    ResponseTickerLink { enabled: bool, peers: usize },
+    ResponseTickerClock { clock: ClockSource },
+    ResponseTickerMetronome { metronome: Metronome },
     ResponseProjectName { name: String },
     ResponseCommAddr { addr: String },
     ResponseCommStatus { established: bool },
+    ResponseCommTargets { targets: HashMap<String, Target>, statuses: HashMap<String, bool>, actives: HashMap<String, String> },
+    ResponseCommRoutes { routes: Vec<Route> },
+    ResponseCommDelivery { delivery: DeliveryPolicies },
+    ResponseCommQueues { queues: HashMap<String, QueueStats> },
+    ResponseCommDmxMappings { mappings: Vec<DmxMapping> },
+    ResponseCommMidiMappings { mappings: Vec<MidiMapping> },
+    ResponseCommSinkOptions { options: HashMap<String, SinkOptions> },
     ResponseCtrlContext { context: Option<String> },
     ResponseAllTracks { tracks: HashMap<String, Track> },
     ResponseAllPatterns { patterns: HashMap<String, Pattern> },
     ResponseAllEvents { events: HashMap<String, Event> },
     ResponseAllSliders { sliders: HashMap<String, Slider> },
+    ResponseSliderRate { rate_hz: f32 },
 
     Notify { severity: Severity, summary: String, detail: String },
 }
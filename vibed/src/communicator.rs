@@ -1,142 +1,1254 @@
-use std::{io, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
-use crate::mosc::MinOscMessage;
-use rosc::{OscPacket, encoder::encode};
+use rosc::{OscBundle, OscPacket, OscTime};
+use serde::{Deserialize, Serialize};
 use tokio::{
-    io::AsyncWriteExt,
-    net::TcpStream,
     select, spawn,
-    sync::{RwLock as AsyncRwLock, mpsc, watch},
+    sync::{
+        RwLock as AsyncRwLock, broadcast,
+        mpsc::{self, error::TrySendError},
+        watch,
+    },
     task::JoinHandle,
-    time::sleep,
+    time::{Instant, interval, sleep_until, timeout},
 };
 use tracing::{info, warn};
 
+use crate::{
+    DEFAULT_TARGET_NAME,
+    command::ClientCommand,
+    models::{DeliveryPolicies, DeliveryPolicy, Route, Target},
+    monitor::{DeliveryOutcome, Monitor, Outgoing},
+    mosc::{MinOscArg, MinOscMessage},
+    sink::{OutputSink, SinkRegistry, TargetAddr},
+    transform::{self, TransformRule},
+};
+
 #[derive(Debug, Clone)]
 pub struct CommunicatorState {
-    pub target_addr: Arc<AsyncRwLock<String>>,
-    pub connected: Arc<AsyncRwLock<bool>>,
+    pub targets: Arc<AsyncRwLock<HashMap<String, Target>>>,
+    pub routes: Arc<AsyncRwLock<Vec<Route>>>,
+    pub delivery: Arc<AsyncRwLock<DeliveryPolicies>>,
+    pub connected: Arc<AsyncRwLock<HashMap<String, bool>>>,
+    pub queues: Arc<AsyncRwLock<HashMap<String, QueueStats>>>,
+    pub active: Arc<AsyncRwLock<HashMap<String, String>>>, // address each target is using
+    pub sinks: SinkRegistry,
+    pub monitor: Monitor,
 }
 
 #[derive(Debug)]
 pub struct CommunicatorArg {
     pub cmd_rx: mpsc::Receiver<CommunicatorCommand>,
-    pub connection_status_tx: watch::Sender<bool>,
+    pub inbound_tx: mpsc::Sender<InboundMessage>,
+    pub connection_status_tx: watch::Sender<bool>, // default target only
+    pub client_cmd_broadcast_tx: broadcast::Sender<ClientCommand>,
 }
 
 #[derive(Debug)]
 pub enum CommunicatorCommand {
-    ChangeTargetAddr { addr: String },
-    SendMessage { msg: MinOscMessage },
+    AddTarget {
+        target: Target,
+    },
+    DeleteTarget {
+        name: String,
+    },
+    ChangeTargetAddr {
+        name: String,
+        addr: String,
+    },
+    SetTargetTiming {
+        name: String,
+        lookahead_ms: u64,
+        latency_ms: u64,
+    },
+    SetTargetFallbacks {
+        name: String,
+        fallbacks: Vec<String>,
+        grace_ms: u64,
+    },
+    SetTargetEcho {
+        name: String,
+        echo_path: Option<String>,
+        auto_latency: bool,
+    },
+    SetTargetTransforms {
+        name: String,
+        transforms: Vec<TransformRule>,
+    },
+    SendMessage {
+        out: Outgoing,
+    },
+    SendTick {
+        outs: Vec<Outgoing>,
+        due: Instant,
+    },
+}
+
+/// Outbound queue status of a target
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueStats {
+    pub depth: usize,
+    pub dropped: u64,
+}
+
+/// Message sent back to us by a target
+#[derive(Debug)]
+pub struct InboundMessage {
+    pub target: String,
+    pub msg: MinOscMessage,
 }
 
+// LYN: Communicator
+
+const LINK_CHANNEL_CAPACITY: usize = 256;
+const OUTBOUND_QUEUE_CAPACITY: usize = 256;
+const QUEUE_REPORT_INTERVAL: Duration = Duration::from_millis(500);
+const RECONNECT_BACKOFF_MIN: Duration = Duration::from_millis(200);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(5);
+
 pub async fn main(state: CommunicatorState, arg: CommunicatorArg) {
     info!("Communicator started");
 
     let CommunicatorState {
-        target_addr,
+        targets,
+        routes,
+        delivery,
         connected,
+        queues,
+        active,
+        sinks,
+        monitor,
     } = state;
     let CommunicatorArg {
         mut cmd_rx,
+        inbound_tx,
         connection_status_tx,
+        client_cmd_broadcast_tx,
     } = arg;
 
-    let update_connection_status = async |status: bool| {
-        *connected.write().await = status;
-        connection_status_tx.send(status).unwrap();
+    let spawn_link = async |target: &Target| {
+        let (link_cmd_tx, link_cmd_rx) = mpsc::channel(LINK_CHANNEL_CAPACITY);
+        connected.write().await.insert(target.name.clone(), false);
+        queues
+            .write()
+            .await
+            .insert(target.name.clone(), QueueStats::default());
+        active
+            .write()
+            .await
+            .insert(target.name.clone(), target.addr.clone());
+        spawn(link(LinkArg {
+            failover: Failover::of(target),
+            timing: Timing::of(target),
+            echo: Echo::of(target),
+            transforms: target.transforms.clone(),
+            cmd_rx: link_cmd_rx,
+            sinks: sinks.clone(),
+            inbound: InboundForwarder {
+                target: target.name.clone(),
+                inbound_tx: inbound_tx.clone(),
+            },
+            status: StatusReporter {
+                name: target.name.clone(),
+                connected: connected.clone(),
+                queues: queues.clone(),
+                active: active.clone(),
+                targets: targets.clone(),
+                connection_status_tx: connection_status_tx.clone(),
+                client_cmd_broadcast_tx: client_cmd_broadcast_tx.clone(),
+                monitor: monitor.clone(),
+            },
+        }));
+        link_cmd_tx
     };
 
-    loop {
-        let addr = (*target_addr.read().await).clone();
+    let mut links = HashMap::new();
+    for target in targets.read().await.values() {
+        links.insert(target.name.clone(), spawn_link(target).await);
+    }
+
+    while let Some(cmd) = cmd_rx.recv().await {
+        match cmd {
+            CommunicatorCommand::AddTarget { target } => {
+                links.insert(target.name.clone(), spawn_link(&target).await);
+                targets.write().await.insert(target.name.clone(), target);
+            }
+            CommunicatorCommand::DeleteTarget { name } => {
+                // dropping the sender shuts the link down
+                links.remove(&name);
+                targets.write().await.remove(&name);
+                connected.write().await.remove(&name);
+                queues.write().await.remove(&name);
+                active.write().await.remove(&name);
+            }
+            CommunicatorCommand::ChangeTargetAddr { name, addr } => {
+                if let Some(target) = targets.write().await.get_mut(&name) {
+                    target.addr = addr.clone();
+                }
+                if let Some(link_cmd_tx) = links.get(&name) {
+                    link_cmd_tx
+                        .send(LinkCommand::ChangeAddr { addr })
+                        .await
+                        .expect("Link panicked!");
+                }
+            }
+            CommunicatorCommand::SetTargetTiming {
+                name,
+                lookahead_ms,
+                latency_ms,
+            } => {
+                let mut targets = targets.write().await;
+                let Some(target) = targets.get_mut(&name) else {
+                    continue;
+                };
+                target.lookahead_ms = lookahead_ms;
+                target.latency_ms = latency_ms;
+                if let Some(link_cmd_tx) = links.get(&name) {
+                    link_cmd_tx
+                        .send(LinkCommand::SetTiming {
+                            timing: Timing::of(target),
+                        })
+                        .await
+                        .expect("Link panicked!");
+                }
+            }
+            CommunicatorCommand::SetTargetFallbacks {
+                name,
+                fallbacks,
+                grace_ms,
+            } => {
+                let mut targets = targets.write().await;
+                let Some(target) = targets.get_mut(&name) else {
+                    continue;
+                };
+                target.fallbacks = fallbacks;
+                target.failover_grace_ms = grace_ms;
+                if let Some(link_cmd_tx) = links.get(&name) {
+                    link_cmd_tx
+                        .send(LinkCommand::SetFailover {
+                            failover: Failover::of(target),
+                        })
+                        .await
+                        .expect("Link panicked!");
+                }
+            }
+            CommunicatorCommand::SetTargetEcho {
+                name,
+                echo_path,
+                auto_latency,
+            } => {
+                let mut targets = targets.write().await;
+                let Some(target) = targets.get_mut(&name) else {
+                    continue;
+                };
+                target.echo_path = echo_path;
+                target.auto_latency = auto_latency;
+                if let Some(link_cmd_tx) = links.get(&name) {
+                    link_cmd_tx
+                        .send(LinkCommand::SetEcho {
+                            echo: Echo::of(target),
+                        })
+                        .await
+                        .expect("Link panicked!");
+                }
+            }
+            CommunicatorCommand::SetTargetTransforms { name, transforms } => {
+                let mut targets = targets.write().await;
+                let Some(target) = targets.get_mut(&name) else {
+                    continue;
+                };
+                target.transforms = transforms.clone();
+                if let Some(link_cmd_tx) = links.get(&name) {
+                    link_cmd_tx
+                        .send(LinkCommand::SetTransforms { transforms })
+                        .await
+                        .expect("Link panicked!");
+                }
+            }
+            CommunicatorCommand::SendMessage { out } => {
+                let policy = delivery.read().await.get(out.source.kind());
+                for name in route(&routes.read().await, &out.msg.path) {
+                    try_send_link(
+                        &links,
+                        &name,
+                        LinkCommand::SendMessage {
+                            out: out.clone(),
+                            policy,
+                        },
+                        &monitor,
+                    );
+                }
+            }
+            CommunicatorCommand::SendTick { outs, due } => {
+                let policy = delivery.read().await.pattern;
+                let routes = routes.read().await;
+                let mut per_target: Vec<(String, Vec<Outgoing>)> = Vec::new();
+                for out in outs {
+                    for name in route(&routes, &out.msg.path) {
+                        match per_target.iter_mut().find(|(target, _)| *target == name) {
+                            Some((_, outs)) => outs.push(out.clone()),
+                            None => per_target.push((name, vec![out.clone()])),
+                        }
+                    }
+                }
+                for (name, outs) in per_target {
+                    try_send_link(
+                        &links,
+                        &name,
+                        LinkCommand::SendTick { outs, due, policy },
+                        &monitor,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Names of the targets a message with the given path goes to
+fn route(routes: &[Route], path: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for route in routes.iter().filter(|route| route.matches(path)) {
+        if !names.contains(&route.target) {
+            names.push(route.target.clone());
+        }
+    }
+    if names.is_empty() {
+        names.push(DEFAULT_TARGET_NAME.to_string());
+    }
+    names
+}
+
+fn try_send_link(
+    links: &HashMap<String, mpsc::Sender<LinkCommand>>,
+    name: &str,
+    cmd: LinkCommand,
+    monitor: &Monitor,
+) {
+    let Some(link_cmd_tx) = links.get(name) else {
+        warn!("Routed to unknown target {}, ignoring: {:?}", name, cmd);
+        for out in cmd.outgoing() {
+            monitor.record(name, out, DeliveryOutcome::Dropped);
+        }
+        return;
+    };
+    match link_cmd_tx.try_send(cmd) {
+        Ok(()) => {}
+        Err(TrySendError::Full(cmd)) => {
+            warn!("Target {} is busy, actively ignoring: {:?}", name, cmd);
+            for out in cmd.outgoing() {
+                monitor.record(name, out, DeliveryOutcome::Dropped);
+            }
+        }
+        Err(TrySendError::Closed(_)) => panic!("Link panicked!"),
+    }
+}
+
+// LYN: Timing
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Timing {
+    lookahead: Duration,
+    latency: Duration,
+}
+
+impl Timing {
+    fn of(target: &Target) -> Self {
+        Self {
+            lookahead: Duration::from_millis(target.lookahead_ms),
+            latency: Duration::from_millis(target.latency_ms),
+        }
+    }
+
+    /// Targets without lookahead nor latency get plain messages right on the tick
+    fn bundled(&self) -> bool {
+        !self.lookahead.is_zero() || !self.latency.is_zero()
+    }
+}
+
+/// Tick messages waiting for their target's lookahead window
+#[derive(Debug)]
+struct ScheduledTick {
+    send_at: Instant,
+    due: Instant,
+    outs: Vec<Outgoing>,
+    policy: DeliveryPolicy,
+}
+
+/// NTP timetag of the given instant, "immediately" if it can't be represented
+fn timetag(at: Instant) -> OscTime {
+    let now = Instant::now();
+    let at = if at >= now {
+        SystemTime::now() + (at - now)
+    } else {
+        SystemTime::now() - (now - at)
+    };
+    OscTime::try_from(at).unwrap_or(OscTime::from((0, 1)))
+}
+
+// LYN: Failover
 
+const FAILBACK_PROBE_INTERVAL: Duration = Duration::from_secs(1);
+pub const MIN_FAILOVER_GRACE_MS: u64 = 100; // shorter, and a link never gets to connect
+
+/// A target's primary address and its fallbacks, in order
+#[derive(Debug, Clone)]
+struct Failover {
+    addrs: Vec<String>, // primary first
+    grace: Duration,
+    active: usize,
+}
+
+impl Failover {
+    fn of(target: &Target) -> Self {
+        Self {
+            addrs: [target.addr.clone()]
+                .into_iter()
+                .chain(target.fallbacks.iter().cloned())
+                .collect(),
+            // NOTE: project files may predate the minimum
+            grace: Duration::from_millis(target.failover_grace_ms.max(MIN_FAILOVER_GRACE_MS)),
+            active: 0,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.addrs.len() > 1
+    }
+
+    fn primary(&self) -> &str {
+        &self.addrs[0]
+    }
+
+    fn active(&self) -> &str {
+        &self.addrs[self.active]
+    }
+
+    fn on_primary(&self) -> bool {
+        self.active == 0
+    }
+
+    /// Moves on to the next address, the primary again after the last fallback
+    fn advance(&mut self) {
+        self.active = (self.active + 1) % self.addrs.len();
+    }
+
+    fn set_primary(&mut self, addr: String) {
+        self.addrs[0] = addr;
+        self.active = 0;
+    }
+
+    /// Replaces the fallbacks, staying on the active address if it's still among them
+    fn replace(&mut self, other: Failover) {
+        let active = self.active().to_string();
+        self.active = other.addrs.iter().position(|a| *a == active).unwrap_or(0);
+        self.addrs = other.addrs;
+        self.grace = other.grace;
+    }
+}
+
+// LYN: Echo
+
+const ECHO_INTERVAL: Duration = Duration::from_secs(1);
+const ECHO_PENDING_MAX: usize = 8;
+const AUTO_LATENCY_HYSTERESIS: Duration = Duration::from_millis(2);
+const ECHO_SEQ_MODULO: u32 = 1 << 24; // echoed back as a float by Pd, keep it exact
+
+/// Where to ping a target, the target sends the same message back
+#[derive(Debug, Clone)]
+struct Echo {
+    path: String,
+    auto_latency: bool, // keep the latency offset at the measured one-way delay plus jitter
+}
+
+impl Echo {
+    fn of(target: &Target) -> Option<Self> {
+        target.echo_path.clone().map(|path| Self {
+            path,
+            auto_latency: target.auto_latency,
+        })
+    }
+}
+
+/// Round-trip time and jitter, smoothed the way RFC 6298 and RFC 3550 do it
+#[derive(Debug, Default)]
+struct EchoStats {
+    seq: u32,
+    pending: VecDeque<(u32, Instant)>,
+    srtt: Option<Duration>,
+    last: Option<Duration>,
+    jitter: Duration,
+}
+
+impl EchoStats {
+    fn ping(&mut self, echo: &Echo) -> MinOscMessage {
+        self.seq = (self.seq + 1) % ECHO_SEQ_MODULO;
+        if self.pending.len() >= ECHO_PENDING_MAX {
+            self.pending.pop_front();
+        }
+        self.pending.push_back((self.seq, Instant::now()));
+        MinOscMessage {
+            path: echo.path.clone(),
+            args: vec![MinOscArg::Int(self.seq as i32)],
+        }
+    }
+
+    /// Takes in an echoed ping, `false` if the message isn't one
+    fn pong(&mut self, echo: &Echo, msg: &MinOscMessage) -> bool {
+        if msg.path != echo.path {
+            return false;
+        }
+        let Some(seq) = msg.args.first().and_then(MinOscArg::as_f32) else {
+            return false;
+        };
+        let Some(index) = self.pending.iter().position(|(s, _)| *s as f32 == seq) else {
+            // late or unknown, still an echo
+            return true;
+        };
+        let (_, sent_at) = self.pending.remove(index).unwrap();
+        let rtt = sent_at.elapsed();
+        self.srtt = Some(match self.srtt {
+            Some(srtt) => srtt * 7 / 8 + rtt / 8,
+            None => rtt,
+        });
+        if let Some(last) = self.last {
+            let diff = rtt.abs_diff(last);
+            self.jitter = (self.jitter * 15 + diff) / 16;
+        }
+        self.last = Some(rtt);
+        true
+    }
+
+    /// One-way delay plus twice the jitter, as latency offset
+    fn latency(&self) -> Option<Duration> {
+        self.srtt.map(|srtt| srtt / 2 + self.jitter * 2)
+    }
+}
+
+// LYN: Outbound Queue
+
+/// Bounded backlog of messages waiting for the target to be (re)connected
+#[derive(Debug)]
+struct OutboundQueue {
+    entries: VecDeque<(Outgoing, DeliveryPolicy)>,
+    dropped: u64,
+}
+
+impl OutboundQueue {
+    fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    fn push(&mut self, out: Outgoing, policy: DeliveryPolicy, status: &StatusReporter) {
+        match policy {
+            DeliveryPolicy::Drop => {
+                warn!(
+                    "Not connected to target, actively ignoring osc message: {:?}",
+                    out.msg
+                );
+                self.dropped += 1;
+                status.record(&out, DeliveryOutcome::Dropped);
+                return;
+            }
+            DeliveryPolicy::Latest => self.entries.retain(|(queued, queued_policy)| {
+                let superseded =
+                    *queued_policy == DeliveryPolicy::Latest && queued.msg.path == out.msg.path;
+                if superseded {
+                    status.record(queued, DeliveryOutcome::Dropped);
+                }
+                !superseded
+            }),
+            DeliveryPolicy::Replay => {}
+        }
+        status.record(&out, DeliveryOutcome::Queued);
+        self.entries.push_back((out, policy));
+        if self.entries.len() > OUTBOUND_QUEUE_CAPACITY {
+            let (out, _) = self.entries.pop_front().unwrap();
+            warn!(
+                "Outbound queue full, dropping oldest osc message: {:?}",
+                out.msg
+            );
+            self.dropped += 1;
+            status.record(&out, DeliveryOutcome::Dropped);
+        }
+    }
+
+    /// Puts back a message that failed to be delivered, as the oldest one
+    fn push_front(&mut self, out: Outgoing, policy: DeliveryPolicy, status: &StatusReporter) {
+        if policy == DeliveryPolicy::Drop || self.entries.len() >= OUTBOUND_QUEUE_CAPACITY {
+            warn!("Failed to deliver, dropping osc message: {:?}", out.msg);
+            self.dropped += 1;
+            status.record(&out, DeliveryOutcome::Dropped);
+            return;
+        }
+        status.record(&out, DeliveryOutcome::Queued);
+        self.entries.push_front((out, policy));
+    }
+
+    fn pop(&mut self) -> Option<(Outgoing, DeliveryPolicy)> {
+        self.entries.pop_front()
+    }
+
+    fn stats(&self) -> QueueStats {
+        QueueStats {
+            depth: self.entries.len(),
+            dropped: self.dropped,
+        }
+    }
+}
+
+// LYN: Link
+
+#[derive(Debug)]
+enum LinkCommand {
+    ChangeAddr {
+        addr: String,
+    },
+    SetFailover {
+        failover: Failover,
+    },
+    SetTiming {
+        timing: Timing,
+    },
+    SetEcho {
+        echo: Option<Echo>,
+    },
+    SetTransforms {
+        transforms: Vec<TransformRule>,
+    },
+    SendMessage {
+        out: Outgoing,
+        policy: DeliveryPolicy,
+    },
+    SendTick {
+        outs: Vec<Outgoing>,
+        due: Instant,
+        policy: DeliveryPolicy,
+    },
+}
+
+impl LinkCommand {
+    /// Messages carried by the command, if any
+    fn outgoing(&self) -> &[Outgoing] {
+        match self {
+            LinkCommand::SendMessage { out, .. } => std::slice::from_ref(out),
+            LinkCommand::SendTick { outs, .. } => outs,
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Debug)]
+struct LinkArg {
+    failover: Failover,
+    timing: Timing,
+    echo: Option<Echo>,
+    transforms: Vec<TransformRule>,
+    cmd_rx: mpsc::Receiver<LinkCommand>,
+    sinks: SinkRegistry,
+    inbound: InboundForwarder,
+    status: StatusReporter,
+}
+
+#[derive(Debug, Clone)]
+struct InboundForwarder {
+    target: String,
+    inbound_tx: mpsc::Sender<InboundMessage>,
+}
+
+impl InboundForwarder {
+    fn deliver(&self, msg: MinOscMessage) {
+        info!("Received from target {}: {:?}", self.target, msg);
+        if let Err(err) = self.inbound_tx.try_send(InboundMessage {
+            target: self.target.clone(),
+            msg,
+        }) {
+            warn!("Failed to forward inbound message: {}", err);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct StatusReporter {
+    name: String,
+    connected: Arc<AsyncRwLock<HashMap<String, bool>>>,
+    queues: Arc<AsyncRwLock<HashMap<String, QueueStats>>>,
+    active: Arc<AsyncRwLock<HashMap<String, String>>>,
+    targets: Arc<AsyncRwLock<HashMap<String, Target>>>,
+    connection_status_tx: watch::Sender<bool>,
+    client_cmd_broadcast_tx: broadcast::Sender<ClientCommand>,
+    monitor: Monitor,
+}
+
+impl StatusReporter {
+    fn record(&self, out: &Outgoing, outcome: DeliveryOutcome) {
+        self.monitor.record(&self.name, out, outcome);
+    }
+
+    async fn update(&self, established: bool) {
+        {
+            let mut connected = self.connected.write().await;
+            // target may have been deleted in the meantime
+            let Some(status) = connected.get_mut(&self.name) else {
+                return;
+            };
+            *status = established;
+        }
+        if self.name == DEFAULT_TARGET_NAME {
+            self.connection_status_tx.send_replace(established);
+        }
+        self.broadcast(ClientCommand::CommTargetStatusChanged {
+            name: self.name.clone(),
+            established,
+        });
+    }
+
+    fn update_echo(&self, stats: &EchoStats) {
+        let Some(srtt) = stats.srtt else {
+            return;
+        };
+        self.broadcast(ClientCommand::CommTargetLatency {
+            name: self.name.clone(),
+            rtt_ms: srtt.as_secs_f32() * 1000.0,
+            jitter_ms: stats.jitter.as_secs_f32() * 1000.0,
+        });
+    }
+
+    /// Saves an automatically adjusted latency offset to the target
+    async fn update_latency(&self, latency: Duration) {
+        let (lookahead_ms, latency_ms) = {
+            let mut targets = self.targets.write().await;
+            let Some(target) = targets.get_mut(&self.name) else {
+                return;
+            };
+            target.latency_ms = latency.as_millis() as u64;
+            (target.lookahead_ms, target.latency_ms)
+        };
+        self.broadcast(ClientCommand::CommTargetTimingChanged {
+            name: self.name.clone(),
+            lookahead_ms,
+            latency_ms,
+        });
+    }
+
+    async fn update_queue(&self, stats: QueueStats) {
+        {
+            let mut queues = self.queues.write().await;
+            let Some(queue) = queues.get_mut(&self.name) else {
+                return;
+            };
+            if *queue == stats {
+                return;
+            }
+            *queue = stats;
+        }
+        self.broadcast(ClientCommand::CommQueueUpdated {
+            name: self.name.clone(),
+            stats,
+        });
+    }
+
+    async fn update_active(&self, failover: &Failover) {
+        {
+            let mut active = self.active.write().await;
+            let Some(addr) = active.get_mut(&self.name) else {
+                return;
+            };
+            if addr == failover.active() {
+                return;
+            }
+            *addr = failover.active().to_string();
+        }
+        self.broadcast(ClientCommand::CommTargetActiveChanged {
+            name: self.name.clone(),
+            addr: failover.active().to_string(),
+            primary: failover.on_primary(),
+        });
+    }
+
+    fn broadcast(&self, cmd: ClientCommand) {
+        if let Err(err) = self.client_cmd_broadcast_tx.send(cmd) {
+            warn!("Failed to broadcast client command: {}", err);
+        };
+    }
+}
+
+#[derive(Debug)]
+enum LinkExit {
+    Dropped,
+    AddrChanged,
+    FailBack(Box<dyn OutputSink>),
+    Closed,
+}
+
+/// Everything a link keeps across reconnects
+#[derive(Debug)]
+struct LinkState {
+    failover: Failover,
+    timing: Timing,
+    echo: Option<Echo>,
+    transforms: Vec<TransformRule>,
+    queue: OutboundQueue,
+    schedule: VecDeque<ScheduledTick>,
+    /// Cleared while connected without timetags, whose ticks have to go out right on time
+    timetagged: bool,
+}
+
+impl LinkState {
+    fn schedule(&mut self, outs: Vec<Outgoing>, due: Instant, policy: DeliveryPolicy) {
+        let lookahead = if self.timetagged {
+            self.timing.lookahead
+        } else {
+            Duration::ZERO
+        };
+        self.schedule.push_back(ScheduledTick {
+            send_at: due.checked_sub(lookahead).unwrap_or(due),
+            due,
+            outs,
+            policy,
+        });
+    }
+
+    /// The message as the target should get it, `None` (recorded as dropped) if filtered out
+    fn transformed(&self, out: &Outgoing, status: &StatusReporter) -> Option<Outgoing> {
+        if self.transforms.is_empty() {
+            return Some(out.clone());
+        }
+        match transform::apply(&self.transforms, out.msg.clone()) {
+            Some(msg) => Some(Outgoing { msg, ..out.clone() }),
+            None => {
+                status.record(out, DeliveryOutcome::Dropped);
+                None
+            }
+        }
+    }
+
+    fn next_send_at(&self) -> Option<Instant> {
+        self.schedule.front().map(|scheduled| scheduled.send_at)
+    }
+
+    fn pop_scheduled(&mut self) -> Vec<ScheduledTick> {
+        let now = Instant::now();
+        let mut ret = Vec::new();
+        while self
+            .schedule
+            .front()
+            .is_some_and(|scheduled| scheduled.send_at <= now)
+        {
+            ret.extend(self.schedule.pop_front());
+        }
+        ret
+    }
+}
+
+/// Keeps one target connected, reconnecting with exponential backoff whenever it drops, and
+/// moving on to the next fallback address once it's been down for longer than the grace period
+async fn link(arg: LinkArg) {
+    let LinkArg {
+        failover,
+        timing,
+        echo,
+        transforms,
+        mut cmd_rx,
+        sinks,
+        inbound,
+        status,
+    } = arg;
+    info!("Link to target {} started", status.name);
+
+    let mut state = LinkState {
+        failover,
+        timing,
+        echo,
+        transforms,
+        queue: OutboundQueue::new(),
+        schedule: VecDeque::new(),
+        timetagged: true,
+    };
+    let mut backoff = RECONNECT_BACKOFF_MIN;
+    let mut retry_at = Some(Instant::now());
+    let mut down_since = Instant::now();
+    let mut connecting: Option<JoinHandle<io::Result<Box<dyn OutputSink>>>> = None;
+    let mut report_interval = interval(QUEUE_REPORT_INTERVAL);
+
+    loop {
         select! {
-            Some(cmd) = cmd_rx.recv() => {
+            cmd = cmd_rx.recv() => {
                 match cmd {
-                    CommunicatorCommand::ChangeTargetAddr { addr } => {
-                        *target_addr.write().await = addr;
+                    Some(LinkCommand::ChangeAddr { addr }) => {
+                        state.failover.set_primary(addr);
+                        status.update_active(&state.failover).await;
+                        if let Some(handle) = connecting.take() {
+                            handle.abort();
+                        }
+                        backoff = RECONNECT_BACKOFF_MIN;
+                        retry_at = Some(Instant::now());
+                        down_since = Instant::now();
+                    }
+                    Some(LinkCommand::SetFailover { failover }) => {
+                        let was = state.failover.active().to_string();
+                        state.failover.replace(failover);
+                        if state.failover.active() != was {
+                            status.update_active(&state.failover).await;
+                            if let Some(handle) = connecting.take() {
+                                handle.abort();
+                            }
+                            retry_at = Some(Instant::now());
+                        }
+                    }
+                    Some(LinkCommand::SetTiming { timing }) => state.timing = timing,
+                    Some(LinkCommand::SetEcho { echo }) => state.echo = echo,
+                    Some(LinkCommand::SetTransforms { transforms }) => state.transforms = transforms,
+                    Some(LinkCommand::SendMessage { out, policy }) => {
+                        state.queue.push(out, policy, &status);
                     }
-                    CommunicatorCommand::SendMessage { msg } => {
-                        warn!("Not connected to TCP server, actively ignoring osc message: {:?}", msg);
+                    Some(LinkCommand::SendTick { outs, due, policy }) => {
+                        state.schedule(outs, due, policy);
                     }
+                    None => break,
                 }
             }
-            Ok(res) = spawn_connect(addr) => {
+            _ = sleep_until(state.next_send_at().unwrap_or_else(Instant::now)),
+                if state.next_send_at().is_some() =>
+            {
+                for scheduled in state.pop_scheduled() {
+                    for out in scheduled.outs {
+                        state.queue.push(out, scheduled.policy, &status);
+                    }
+                }
+            }
+            _ = report_interval.tick() => status.update_queue(state.queue.stats()).await,
+            _ = sleep_until(down_since + state.failover.grace), if state.failover.enabled() => {
+                if let Some(handle) = connecting.take() {
+                    handle.abort();
+                }
+                let unreachable = state.failover.active().to_string();
+                state.failover.advance();
+                warn!(
+                    "Target {} unreachable at {} for {:?}, switching to {}",
+                    status.name,
+                    unreachable,
+                    state.failover.grace,
+                    state.failover.active()
+                );
+                status.update_active(&state.failover).await;
+                backoff = RECONNECT_BACKOFF_MIN;
+                retry_at = Some(Instant::now());
+                down_since = Instant::now();
+            }
+            _ = sleep_until(retry_at.unwrap_or_else(Instant::now)),
+                if retry_at.is_some() && connecting.is_none() =>
+            {
+                retry_at = None;
+                let addr = state.failover.active();
+                match sinks.parse(addr) {
+                    Ok(target) => connecting = Some(spawn_connect(target, &sinks)),
+                    // wait for a new address
+                    Err(err) => warn!("Invalid target address \"{}\": {}", addr, err),
+                }
+            }
+            res = async { connecting.as_mut().unwrap().await }, if connecting.is_some() => {
+                connecting = None;
                 match res {
-                    Ok(stream) => {
-                        update_connection_status(true).await;
-                        if let Some(addr) = process(stream, &mut cmd_rx).await {
-                            *target_addr.write().await = addr;
+                    Ok(Ok(mut sink)) => {
+                        backoff = RECONNECT_BACKOFF_MIN;
+                        status.update(true).await;
+                        let mut up_since = Instant::now();
+                        let mut connectionless;
+                        let exit = loop {
+                            state.timetagged = sink.timetagged();
+                            connectionless = sink.connectionless();
+                            let exit = process(sink, &mut cmd_rx, &sinks, &inbound, &mut state, &status).await;
+                            state.timetagged = true;
+                            let LinkExit::FailBack(primary) = exit else {
+                                break exit;
+                            };
+                            // switch over without ever being disconnected
+                            info!("Target {} is back at its primary address", status.name);
+                            state.failover.active = 0;
+                            status.update_active(&state.failover).await;
+                            sink = primary;
+                            up_since = Instant::now();
                         };
-                        update_connection_status(false).await;
+                        status.update(false).await;
+                        status.update_queue(state.queue.stats()).await;
+                        match exit {
+                            LinkExit::Dropped => retry_at = Some(Instant::now() + backoff),
+                            LinkExit::AddrChanged => {
+                                status.update_active(&state.failover).await;
+                                retry_at = Some(Instant::now());
+                            }
+                            LinkExit::FailBack(_) => unreachable!(),
+                            LinkExit::Closed => break,
+                        }
+                        // NOTE: a connectionless sink comes up either way, the address only
+                        // counts as reachable if it stayed up for a while
+                        if !connectionless || up_since.elapsed() >= state.failover.grace {
+                            down_since = Instant::now();
+                        }
+                    }
+                    Ok(Err(err)) => {
+                        warn!("{:?}, retrying in {:?}", err, backoff);
+                        retry_at = Some(Instant::now() + backoff);
+                        backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
+                    }
+                    Err(err) => {
+                        warn!("Connecting task failed: {}", err);
+                        retry_at = Some(Instant::now() + backoff);
                     }
-                    Err(err) => warn!("{:?}", err),
                 }
-                sleep(Duration::from_millis(200)).await;
             }
         }
     }
+    info!("Link to target {} stopped", status.name);
+}
+
+/// Sends the tick's messages in a bundle timetagged `due` plus latency, or one by one
+async fn send_scheduled(
+    sink: &mut dyn OutputSink,
+    outs: &[Outgoing],
+    due: Instant,
+    timing: Timing,
+) -> io::Result<()> {
+    if outs.is_empty() {
+        return Ok(());
+    }
+    if !timing.bundled() || !sink.timetagged() {
+        for out in outs {
+            sink.send(&OscPacket::Message(out.msg.clone().into()))
+                .await?;
+        }
+        return Ok(());
+    }
+    let bundle = OscBundle {
+        timetag: timetag(due + timing.latency),
+        content: outs
+            .iter()
+            .map(|out| OscPacket::Message(out.msg.clone().into()))
+            .collect(),
+    };
+    sink.send(&OscPacket::Bundle(bundle)).await
 }
 
 // HACK: It seems that making tcp connection isn't canceal safe to be used in inside `select!`,
 // thus must be wrapped inside a `tokio::spawn()`
-fn spawn_connect(addr: String) -> JoinHandle<io::Result<TcpStream>> {
-    spawn(async move { TcpStream::connect(addr).await })
+fn spawn_connect(
+    target: TargetAddr,
+    sinks: &SinkRegistry,
+) -> JoinHandle<io::Result<Box<dyn OutputSink>>> {
+    spawn(sinks.connect(target))
+}
+
+/// Connects to the primary again, only handing it out once it's known to be back
+///
+/// A connectionless sink comes up whether anyone's there or not, so it has to answer an echo
+/// ping first. Without an echo path there's no telling, and the probe fails as `Unsupported`.
+fn spawn_probe(
+    target: TargetAddr,
+    sinks: &SinkRegistry,
+    echo: Option<Echo>,
+) -> JoinHandle<io::Result<Box<dyn OutputSink>>> {
+    let connect = sinks.connect(target);
+    spawn(async move {
+        let mut sink = connect.await?;
+        if !sink.connectionless() {
+            return Ok(sink);
+        }
+        let Some(echo) = echo else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "can't tell whether a connectionless primary is back without an echo path",
+            ));
+        };
+        let mut stats = EchoStats::default();
+        sink.send(&OscPacket::Message(stats.ping(&echo).into()))
+            .await?;
+        let answered = timeout(FAILBACK_PROBE_INTERVAL, async {
+            loop {
+                if sink.recv().await?.iter().any(|msg| stats.pong(&echo, msg)) {
+                    return io::Result::Ok(());
+                }
+            }
+        });
+        match answered.await {
+            Ok(res) => res.map(|_| sink),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "no echo from the primary",
+            )),
+        }
+    })
 }
 
 async fn process(
-    mut stream: TcpStream,
-    cmd_rx: &mut mpsc::Receiver<CommunicatorCommand>,
-) -> Option<String> {
-    loop {
+    mut sink: Box<dyn OutputSink>,
+    cmd_rx: &mut mpsc::Receiver<LinkCommand>,
+    sinks: &SinkRegistry,
+    inbound: &InboundForwarder,
+    state: &mut LinkState,
+    status: &StatusReporter,
+) -> LinkExit {
+    // replay what's been held back while disconnected
+    while let Some((out, policy)) = state.queue.pop() {
+        let Some(sent) = state.transformed(&out, status) else {
+            continue;
+        };
+        if let Err(err) = sink
+            .send(&OscPacket::Message(sent.msg.clone().into()))
+            .await
+        {
+            warn!("Failed to write message {:?}", err);
+            state.queue.push_front(out, policy, status);
+            return LinkExit::Dropped;
+        }
+        status.record(&sent, DeliveryOutcome::Sent);
+    }
+    status.update_queue(state.queue.stats()).await;
+
+    let mut echo_stats = EchoStats::default();
+    let mut echo_interval = interval(ECHO_INTERVAL);
+
+    // while on a fallback, keep checking whether the primary is back
+    let mut probe: Option<JoinHandle<io::Result<Box<dyn OutputSink>>>> = None;
+    let mut probe_at =
+        (!state.failover.on_primary()).then(|| Instant::now() + FAILBACK_PROBE_INTERVAL);
+
+    let exit = loop {
         select! {
-            Some(cmd) = cmd_rx.recv() => {
+            cmd = cmd_rx.recv() => {
                 match cmd {
-                    CommunicatorCommand::ChangeTargetAddr { addr } => break Some(addr),
-                    CommunicatorCommand::SendMessage { msg } => {
-                        // FIXME: handle invalid osc message error
-                        let packat = encode(&OscPacket::Message(msg.into())).unwrap();
-                        let packet: String = packat
-                            .iter()
-                            .map(|b| b.to_string())
-                            .collect::<Vec<_>>()
-                            .join(" ")
-                            + ";";
-                        if let Err(err) = stream.write_all(packet.as_bytes()).await {
-                            // WARN: not sure how to deal with failure
-                            warn!(
-                                "Failed to write message {:?}, don't know how to do about it",
-                                err
-                            );
-                            break None;
+                    None => break LinkExit::Closed,
+                    Some(LinkCommand::ChangeAddr { addr }) => {
+                        state.failover.set_primary(addr);
+                        break LinkExit::AddrChanged;
+                    }
+                    Some(LinkCommand::SetFailover { failover }) => {
+                        let was = state.failover.active().to_string();
+                        state.failover.replace(failover);
+                        if state.failover.active() != was {
+                            break LinkExit::AddrChanged;
+                        }
+                    }
+                    Some(LinkCommand::SetTiming { timing }) => state.timing = timing,
+                    Some(LinkCommand::SetEcho { echo }) => {
+                        state.echo = echo;
+                        echo_stats = EchoStats::default();
+                        if !state.failover.on_primary() && probe.is_none() && probe_at.is_none() {
+                            probe_at = Some(Instant::now() + FAILBACK_PROBE_INTERVAL);
+                        }
+                    }
+                    Some(LinkCommand::SetTransforms { transforms }) => state.transforms = transforms,
+                    Some(LinkCommand::SendMessage { out, policy }) => {
+                        let Some(sent) = state.transformed(&out, status) else {
+                            continue;
+                        };
+                        if let Err(err) = sink.send(&OscPacket::Message(sent.msg.clone().into())).await {
+                            warn!("Failed to write message {:?}", err);
+                            state.queue.push_front(out, policy, status);
+                            break LinkExit::Dropped;
                         }
+                        status.record(&sent, DeliveryOutcome::Sent);
+                    }
+                    Some(LinkCommand::SendTick { outs, due, policy }) => {
+                        state.schedule(outs, due, policy);
                     }
                 }
             }
-            Err(err) = stream.writable() => {
-                // WARN: not sure how to deal with failure
-                warn!("stream unwritable {:?}, don't know how to do about it", err);
-                break None;
+            _ = sleep_until(probe_at.unwrap_or_else(Instant::now)),
+                if probe_at.is_some() && probe.is_none() =>
+            {
+                probe_at = None;
+                if let Ok(target) = sinks.parse(state.failover.primary()) {
+                    probe = Some(spawn_probe(target, sinks, state.echo.clone()));
+                }
             }
-            read_ready = stream.readable() => {
-                if let Err(err) = read_ready {
-                    warn!("socket unreadable {:?}, don't know how to do about it", err);
-                    break None;
+            res = async { probe.as_mut().unwrap().await }, if probe.is_some() => {
+                probe = None;
+                match res {
+                    Ok(Ok(primary)) => break LinkExit::FailBack(primary),
+                    // stays on the fallback until an echo path is set
+                    Ok(Err(err)) if err.kind() == io::ErrorKind::Unsupported => {
+                        info!("Target {} won't fail back: {}", status.name, err);
+                    }
+                    _ => probe_at = Some(Instant::now() + FAILBACK_PROBE_INTERVAL),
                 }
-                let mut buf = [0u8; 16];
-                match stream.try_read(&mut buf) {
-                    Ok(0) => {
-                        warn!("socket connection closed by peer");
-                        break None;
+            }
+            _ = sleep_until(state.next_send_at().unwrap_or_else(Instant::now)),
+                if state.next_send_at().is_some() =>
+            {
+                let mut failed = false;
+                for scheduled in state.pop_scheduled() {
+                    if failed {
+                        for out in scheduled.outs {
+                            state.queue.push(out, scheduled.policy, status);
+                        }
+                        continue;
                     }
-                    Ok(_) => {}
-                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
-                    Err(err) => {
-                        warn!("read error {:?}, don't know how to do about it", err);
-                        break None;
+                    let sent = scheduled
+                        .outs
+                        .iter()
+                        .filter_map(|out| state.transformed(out, status))
+                        .collect::<Vec<_>>();
+                    if let Err(err) = send_scheduled(sink.as_mut(), &sent, scheduled.due, state.timing).await {
+                        warn!("Failed to write tick messages {:?}", err);
+                        for out in scheduled.outs.into_iter().rev() {
+                            state.queue.push_front(out, scheduled.policy, status);
+                        }
+                        failed = true;
+                    } else {
+                        for out in &sent {
+                            status.record(out, DeliveryOutcome::Sent);
+                        }
                     }
                 }
+                if failed {
+                    break LinkExit::Dropped;
+                }
+            }
+            _ = echo_interval.tick(), if state.echo.is_some() => {
+                let ping = echo_stats.ping(state.echo.as_ref().unwrap());
+                if let Err(err) = sink.send(&OscPacket::Message(ping.into())).await {
+                    warn!("Failed to write echo ping {:?}", err);
+                    break LinkExit::Dropped;
+                }
             }
+            msgs = sink.recv() => match msgs {
+                Ok(msgs) => {
+                    for msg in msgs {
+                        let Some(echo) = &state.echo else {
+                            inbound.deliver(msg);
+                            continue;
+                        };
+                        if !echo_stats.pong(echo, &msg) {
+                            inbound.deliver(msg);
+                            continue;
+                        }
+                        status.update_echo(&echo_stats);
+                        if echo.auto_latency
+                            && let Some(latency) = echo_stats.latency()
+                            && latency.abs_diff(state.timing.latency) >= AUTO_LATENCY_HYSTERESIS
+                        {
+                            state.timing.latency = latency;
+                            status.update_latency(latency).await;
+                        }
+                    }
+                }
+                Err(err) => {
+                    warn!("Target {} dropped: {:?}", status.name, err);
+                    break LinkExit::Dropped;
+                }
+            },
         }
+    };
+    if let Some(probe) = probe {
+        probe.abort();
     }
+    exit
 }
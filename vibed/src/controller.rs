@@ -1,14 +1,22 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::VecDeque, sync::Arc, time::Duration};
 
 use tokio::{
     select,
     sync::{RwLock as AsyncRwLock, broadcast, mpsc, watch},
-    time::{Instant, interval_at},
+    time::{Instant, interval_at, sleep_until},
 };
 use tracing::{info, warn};
 
 use crate::{
-    DEFAULT_SAVE_PATH, command::ClientCommand, communicator::CommunicatorCommand, store::Store,
+    DEFAULT_SAVE_PATH,
+    command::ClientCommand,
+    communicator::{CommunicatorCommand, InboundMessage},
+    models::MessageSource,
+    monitor::Outgoing,
+    mosc::{MinOscArg, MinOscMessage},
+    store::Store,
+    throttle::Throttle,
+    ticker::{Click, Tick, TickerCommand},
 };
 
 #[derive(Debug, Clone)]
@@ -20,14 +28,31 @@ pub struct ControllerState {
 pub struct ControllerArg {
     pub store: Store,
     pub cmd_rx: mpsc::Receiver<ControllerCommand>,
-    pub tick_rx: watch::Receiver<(Option<usize>, usize)>,
+    pub tick_rx: watch::Receiver<Tick>,
+    pub inbound_rx: mpsc::Receiver<InboundMessage>,
+    pub ticker_cmd_tx: mpsc::Sender<TickerCommand>,
     pub communicator_cmd_tx: mpsc::Sender<CommunicatorCommand>,
     pub client_cmd_broadcast_tx: broadcast::Sender<ClientCommand>,
 }
 
 #[derive(Debug)]
 pub enum ControllerCommand {
-    ChangeContext { context: Option<String> },
+    ChangeContext {
+        context: Option<String>,
+    },
+    MoveSlider {
+        name: String,
+        path: String,
+        val: f32,
+    },
+}
+
+/// A slider's new value, waiting to be sent to targets
+#[derive(Debug)]
+struct SliderMove {
+    name: String,
+    path: String,
+    val: f32,
 }
 
 pub async fn main(state: ControllerState, arg: ControllerArg) {
@@ -38,11 +63,20 @@ pub async fn main(state: ControllerState, arg: ControllerArg) {
         store,
         mut cmd_rx,
         mut tick_rx,
+        mut inbound_rx,
+        ticker_cmd_tx,
         communicator_cmd_tx,
         client_cmd_broadcast_tx,
     } = arg;
 
     let mut interval = interval_at(Instant::now(), Duration::from_secs(10));
+    // NOTE: output coalesces per path, but sliders sharing a path each get their final value
+    // broadcast, so clients don't keep showing whichever lost
+    let slider_rate_hz = *store.slider_rate_hz.read().await;
+    let mut slider_throttle = Throttle::new(slider_rate_hz);
+    let mut slider_val_throttle = Throttle::new(slider_rate_hz);
+    // track updates for clients, held back until their tick is due
+    let mut pending: VecDeque<(Instant, ClientCommand)> = VecDeque::new();
 
     loop {
         select! {
@@ -56,10 +90,99 @@ pub async fn main(state: ControllerState, arg: ControllerArg) {
                     ControllerCommand::ChangeContext { context: new_context } => {
                         *context.write().await = new_context;
                     }
+                    ControllerCommand::MoveSlider { name, path, val } => {
+                        let slider_rate_hz = *store.slider_rate_hz.read().await;
+                        slider_throttle.set_rate(slider_rate_hz);
+                        slider_val_throttle.set_rate(slider_rate_hz);
+                        if let Some((name, val)) = slider_val_throttle.push(&name, (name.clone(), val)) {
+                            broadcast_slider_val(name, val, &client_cmd_broadcast_tx);
+                        }
+                        let key = path.clone();
+                        if let Some(slider_move) = slider_throttle.push(&key, SliderMove { name, path, val }) {
+                            move_slider(slider_move, &communicator_cmd_tx).await;
+                        }
+                    }
+                }
+            }
+            _ = sleep_until(slider_due(&slider_throttle, &slider_val_throttle).unwrap_or_else(Instant::now)),
+                if slider_due(&slider_throttle, &slider_val_throttle).is_some() =>
+            {
+                for (name, val) in slider_val_throttle.pop_due() {
+                    broadcast_slider_val(name, val, &client_cmd_broadcast_tx);
+                }
+                for slider_move in slider_throttle.pop_due() {
+                    move_slider(slider_move, &communicator_cmd_tx).await;
+                }
+            }
+            _ = sleep_until(pending.front().map_or_else(Instant::now, |(due, _)| *due)),
+                if !pending.is_empty() =>
+            {
+                let (due, _) = pending.front().unwrap();
+                let due = *due;
+                while let Some((_, cmd)) = pending.pop_front_if(|(at, _)| *at <= due) {
+                    if let Err(err) = client_cmd_broadcast_tx.send(cmd) {
+                        warn!("Failed to broadcast client command: {}", err);
+                    };
+                }
+            }
+            Some(InboundMessage { target, msg }) = inbound_rx.recv() => {
+                // clock messages drive the ticker, they'd only flood clients
+                let events = store.clock.read().await.events(&msg);
+                if !events.is_empty() {
+                    let at = Instant::now();
+                    for event in events {
+                        ticker_cmd_tx
+                            .send(TickerCommand::Clock { event, at })
+                            .await
+                            .expect("Ticker panicked!");
+                    }
+                    continue;
+                }
+                if let Some(val) = msg.args.first().and_then(MinOscArg::as_f32) {
+                    for slider in store
+                        .sliders
+                        .write()
+                        .await
+                        .values_mut()
+                        .filter(|slider| slider.feedback && slider.path == msg.path)
+                    {
+                        // NOTE: not `clamp`, which panics on a slider whose min is above its max
+                        slider.val = val.max(slider.min).min(slider.max);
+                        if let Err(err) = client_cmd_broadcast_tx.send(ClientCommand::SliderValSet {
+                            name: slider.name.clone(),
+                            val: slider.val,
+                        }) {
+                            warn!("Failed to broadcast client command: {}", err);
+                        };
+                    }
                 }
+                if let Err(err) = client_cmd_broadcast_tx.send(ClientCommand::CommMessageReceived {
+                    target,
+                    msg,
+                }) {
+                    warn!("Failed to broadcast client command: {}", err);
+                };
             }
             Ok(()) = tick_rx.changed() => {
-                let (Some(tick), _) = *tick_rx.borrow_and_update() else {
+                let Tick { tick, due, resolution, click, .. } = *tick_rx.borrow_and_update();
+                let clicks: Vec<Outgoing> = if let Some(click) = click {
+                    let metronome = store.metronome.read().await;
+                    let msg = match click {
+                        Click::Accent => metronome.accent.clone(),
+                        Click::Beat => metronome.beat.clone(),
+                    };
+                    vec![Outgoing { msg, source: MessageSource::Metronome, tick }]
+                } else {
+                    vec![]
+                };
+                let Some(tick) = tick else {
+                    // stopped or counting in, track updates still ahead are moot
+                    pending.clear();
+                    if !clicks.is_empty() {
+                        communicator_cmd_tx.send(CommunicatorCommand::SendTick { outs: clicks, due })
+                            .await
+                            .expect("Communicator panicked!");
+                    }
                     continue;
                 };
 
@@ -69,43 +192,96 @@ pub async fn main(state: ControllerState, arg: ControllerArg) {
                         warn!("Pattern {} not found", pattern_name);
                         continue;
                     };
-                    for msg in pattern.get_osc_messages(tick) {
-                        communicator_cmd_tx.send(CommunicatorCommand::SendMessage { msg })
-                            .await
-                            .expect("Communicator panicked!");
-                    }
+                    communicator_cmd_tx.send(CommunicatorCommand::SendTick {
+                        outs: clicks
+                            .into_iter()
+                            .chain(pattern.get_osc_messages(tick, resolution).into_iter().map(|msg| Outgoing {
+                                msg,
+                                source: MessageSource::Pattern(pattern.name.clone()),
+                                tick: Some(tick),
+                            }))
+                            .collect(),
+                        due,
+                    })
+                        .await
+                        .expect("Communicator panicked!");
                 } else {
+                    let signature = *store.time_signature.read().await;
                     let mut tracks = store.tracks.write().await;
-                    let mut msgs = Vec::new();
+                    let mut outs = clicks;
                     for (_, track) in tracks.iter_mut().filter(|(_, t)| t.active || t.progress.is_some()) {
-                        msgs.push(
+                        outs.extend(
                             track
-                                .get_osc_messages_and_advance(tick, store.patterns.clone())
+                                .get_osc_messages_and_advance(tick, resolution, &signature, store.patterns.clone())
                                 .await
+                                .into_iter()
+                                .map(|msg| Outgoing {
+                                    msg,
+                                    source: MessageSource::Track(track.name.clone()),
+                                    tick: Some(tick),
+                                })
                         );
 
-                        if let Err(err) = client_cmd_broadcast_tx.send(ClientCommand::TrackProgressUpdate {
+                        pending.push_back((due, ClientCommand::TrackProgressUpdate {
                             name: track.name.clone(),
                             progress: track.progress,
-                        }) {
-                            warn!("Failed to broadcast client command: {}", err);
-                        };
+                        }));
                         if !track.active {
-                            if let Err(err) =client_cmd_broadcast_tx.send(ClientCommand::TrackMadeActive {
+                            pending.push_back((due, ClientCommand::TrackMadeActive {
                                 name: track.name.clone(),
                                 active: false,
-                            }) {
-                            warn!("Failed to broadcast client command: {}", err);
-                        };
+                            }));
                         }
                     }
-                    for msg in msgs.iter().flatten() {
-                        communicator_cmd_tx.send(CommunicatorCommand::SendMessage {
-                            msg: msg.clone()
-                        }).await.expect("Communicator panicked!");
-                    }
+                    communicator_cmd_tx.send(CommunicatorCommand::SendTick {
+                        outs,
+                        due,
+                    }).await.expect("Communicator panicked!");
                 }
             }
         }
     }
 }
+
+/// Whichever of the slider throttles has something due first
+fn slider_due(
+    slider_throttle: &Throttle<SliderMove>,
+    slider_val_throttle: &Throttle<(String, f32)>,
+) -> Option<Instant> {
+    [slider_throttle.next_due(), slider_val_throttle.next_due()]
+        .into_iter()
+        .flatten()
+        .min()
+}
+
+/// Tells clients about a slider's value, once it got through the throttle
+fn broadcast_slider_val(
+    name: String,
+    val: f32,
+    client_cmd_broadcast_tx: &broadcast::Sender<ClientCommand>,
+) {
+    if let Err(err) = client_cmd_broadcast_tx.send(ClientCommand::SliderValSet { name, val }) {
+        warn!("Failed to broadcast client command: {}", err);
+    };
+}
+
+/// Tells targets about a slider's value, once it got through the throttle
+async fn move_slider(
+    slider_move: SliderMove,
+    communicator_cmd_tx: &mpsc::Sender<CommunicatorCommand>,
+) {
+    let SliderMove { name, path, val } = slider_move;
+    communicator_cmd_tx
+        .send(CommunicatorCommand::SendMessage {
+            out: Outgoing {
+                msg: MinOscMessage {
+                    path,
+                    args: vec![MinOscArg::Float(val)],
+                },
+                source: MessageSource::Slider(name),
+                tick: None,
+            },
+        })
+        .await
+        .expect("Communicator panicked!");
+}
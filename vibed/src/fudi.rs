@@ -0,0 +1,184 @@
+use std::io;
+
+use tracing::warn;
+
+use crate::{
+    mosc::{MinOscArg, MinOscMessage},
+    sink::{MAX_FRAME_SIZE, frame_too_large},
+};
+
+/// Writes a message as one plain FUDI line, the path (minus its leading `/`) becoming the
+/// selector, e.g. `/drums/kick 64` as `drums/kick 64;` for a `[route drums/kick]` in Pd
+pub fn encode(msg: &MinOscMessage) -> Vec<u8> {
+    let mut atoms = Vec::with_capacity(msg.args.len() + 1);
+    let selector = msg.path.trim_start_matches('/');
+    if !selector.is_empty() {
+        atoms.push(escape(selector));
+    }
+    for arg in &msg.args {
+        match arg {
+            MinOscArg::Int(i) => atoms.push(i.to_string()),
+            MinOscArg::Float(f) => atoms.push(f.to_string()),
+            MinOscArg::String(s) if s.is_empty() => warn!("Pd has no empty symbol, skipping"),
+            MinOscArg::String(s) => atoms.push(escape(s)),
+            MinOscArg::Bool(b) => atoms.push((*b as u8).to_string()),
+            MinOscArg::Nil => {}
+            MinOscArg::Blob(bytes) => atoms.extend(bytes.iter().map(u8::to_string)),
+            MinOscArg::Long(l) => atoms.push(l.to_string()),
+            MinOscArg::Double(d) => atoms.push(d.to_string()),
+        }
+    }
+    if atoms.is_empty() {
+        atoms.push(String::from("bang"));
+    }
+    (atoms.join(" ") + ";\n").into_bytes()
+}
+
+/// Escapes characters Pd would otherwise take as atom or message separators
+fn escape(atom: &str) -> String {
+    let mut ret = String::with_capacity(atom.len());
+    for c in atom.chars() {
+        if matches!(c, ' ' | '\t' | '\n' | ',' | ';' | '\\' | '$') {
+            ret.push('\\');
+        }
+        ret.push(c);
+    }
+    ret
+}
+
+/// Splits an inbound FUDI text stream into messages, the reverse of [`encode`]
+#[derive(Debug, Default)]
+pub struct Deframer {
+    buf: Vec<u8>,
+}
+
+impl Deframer {
+    /// Errors once a message grows past [`MAX_FRAME_SIZE`] without its terminator
+    pub fn push(&mut self, bytes: &[u8]) -> io::Result<Vec<MinOscMessage>> {
+        self.buf.extend_from_slice(bytes);
+        let mut msgs = Vec::new();
+        let mut start = 0;
+        let mut escaped = false;
+        for i in 0..self.buf.len() {
+            match self.buf[i] {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b';' | b',' => {
+                    let text = String::from_utf8_lossy(&self.buf[start..i]);
+                    msgs.extend(decode(&text));
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        self.buf.drain(..start);
+        if self.buf.len() > MAX_FRAME_SIZE {
+            return Err(frame_too_large(self.buf.len()));
+        }
+        Ok(msgs)
+    }
+}
+
+/// Parses one message (without its terminator), numbers become floats as they do in Pd
+fn decode(text: &str) -> Option<MinOscMessage> {
+    let mut atoms = Vec::new();
+    let mut atom = String::new();
+    let mut quoted = false; // an escaped char makes the atom a symbol, even if it looks numeric
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                atom.extend(chars.next());
+                quoted = true;
+            }
+            c if c.is_whitespace() => {
+                if !atom.is_empty() {
+                    atoms.push((std::mem::take(&mut atom), quoted));
+                }
+                quoted = false;
+            }
+            c => atom.push(c),
+        }
+    }
+    if !atom.is_empty() {
+        atoms.push((atom, quoted));
+    }
+
+    let mut atoms = atoms.into_iter();
+    let (selector, _) = atoms.next()?;
+    Some(MinOscMessage {
+        path: format!("/{}", selector),
+        args: atoms
+            .map(|(atom, quoted)| match atom.parse::<f32>() {
+                Ok(f) if !quoted && f.is_finite() => MinOscArg::Float(f),
+                _ => MinOscArg::String(atom),
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(path: &str, args: Vec<MinOscArg>) -> MinOscMessage {
+        MinOscMessage {
+            path: path.to_string(),
+            args,
+        }
+    }
+
+    #[test]
+    fn encodes_selector_and_atoms() {
+        let encoded = encode(&msg(
+            "/drums/kick",
+            vec![
+                MinOscArg::Int(64),
+                MinOscArg::Float(0.5),
+                MinOscArg::Bool(true),
+            ],
+        ));
+        assert_eq!(encoded, b"drums/kick 64 0.5 1;\n");
+        assert_eq!(encode(&msg("/", vec![])), b"bang;\n");
+    }
+
+    #[test]
+    fn escaping_round_trip() {
+        let text = "a b;c,d\\e$1";
+        let encoded = encode(&msg("/say it", vec![MinOscArg::String(text.to_string())]));
+        assert_eq!(encoded, b"say\\ it a\\ b\\;c\\,d\\\\e\\$1;\n");
+
+        let decoded = Deframer::default().push(&encoded).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].path, "/say it");
+        assert_eq!(decoded[0].args, vec![MinOscArg::String(text.to_string())]);
+    }
+
+    #[test]
+    fn escaped_numbers_stay_symbols() {
+        let decoded = Deframer::default().push(b"n 42 \\42;").unwrap();
+        assert_eq!(
+            decoded[0].args,
+            vec![MinOscArg::Float(42.0), MinOscArg::String("42".to_string())]
+        );
+    }
+
+    #[test]
+    fn messages_across_reads() {
+        let mut deframer = Deframer::default();
+        assert_eq!(deframer.push(b"a 1; b\\").unwrap().len(), 1);
+        let decoded = deframer.push(b"; 2;\n").unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].path, "/b;");
+        assert_eq!(decoded[0].args, vec![MinOscArg::Float(2.0)]);
+    }
+
+    #[test]
+    fn unterminated_text_is_refused() {
+        assert!(
+            Deframer::default()
+                .push(&vec![b'a'; MAX_FRAME_SIZE + 1])
+                .is_err()
+        );
+    }
+}
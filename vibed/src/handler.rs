@@ -1,4 +1,4 @@
-use std::net::SocketAddr;
+use std::{collections::VecDeque, net::SocketAddr};
 
 use axum::{
     extract::{
@@ -10,34 +10,44 @@ use axum::{
 use tokio::{
     select,
     sync::{broadcast, mpsc, watch},
+    time::{Instant, sleep_until},
 };
 use tracing::{info, warn};
 
 use crate::{
+    DEFAULT_TARGET_NAME, MAX_BPM, MIN_BPM, MIN_SLIDER_RATE_HZ,
+    ableton::{AbletonCommand, AbletonState},
+    bridge::OscBridge,
     command::{ClientCommand, ServerCommand, Severity},
-    communicator::{CommunicatorCommand, CommunicatorState},
+    communicator::{CommunicatorCommand, CommunicatorState, MIN_FAILOVER_GRACE_MS},
     controller::{ControllerCommand, ControllerState},
-    models::{Event, Pattern, Slider, Track},
-    mosc::{MinOscArg, MinOscMessage},
+    models::{Event, MAX_RESOLUTION, MessageSource, Pattern, Slider, Target, Track},
+    monitor::{MonitorSubscription, Outgoing},
+    mosc::MinOscMessage,
     store::Store,
-    ticker::{TickerCommand, TickerState},
+    ticker::{ClockSource, Tick, TickerCommand, TickerState},
 };
 
+const MAX_NUDGE_MS: i32 = 100;
+
 #[derive(Debug, Clone)]
 pub struct HandlerState {
     pub store: Store,
 
-    pub tick_rx: watch::Receiver<(Option<usize>, usize)>,
+    pub tick_rx: watch::Receiver<Tick>,
     pub connection_status_rx: watch::Receiver<bool>,
 
     pub ticker_cmd_tx: mpsc::Sender<TickerCommand>,
+    pub ableton_cmd_tx: mpsc::Sender<AbletonCommand>,
     pub controller_cmd_tx: mpsc::Sender<ControllerCommand>,
     pub communicator_cmd_tx: mpsc::Sender<CommunicatorCommand>,
     pub client_cmd_broadcast: broadcast::Sender<ClientCommand>,
 
     pub ticker_state: TickerState,
+    pub ableton_state: AbletonState,
     pub controller_state: ControllerState,
     pub communicator_state: CommunicatorState,
+    pub bridge: OscBridge,
 }
 
 pub async fn ws_upgrader(
@@ -56,16 +66,23 @@ async fn ws_handler(mut socket: WebSocket, addr: SocketAddr, state: HandlerState
         mut tick_rx,
         mut connection_status_rx,
         ticker_cmd_tx,
+        ableton_cmd_tx,
         communicator_cmd_tx,
         controller_cmd_tx,
         client_cmd_broadcast,
         ticker_state,
+        ableton_state,
         controller_state,
         communicator_state,
+        ..
     } = state;
 
     let client_cmd_broadcast_tx = client_cmd_broadcast.clone();
     let mut client_cmd_broadcast_rx = client_cmd_broadcast.subscribe();
+    let mut monitor: Option<MonitorSubscription> = None;
+    // NOTE: ticks come in ahead of time by the targets' lookahead, the playhead moves when
+    // they're due
+    let mut pending_ticks: VecDeque<(Instant, ClientCommand)> = VecDeque::new();
 
     loop {
         select! {
@@ -87,12 +104,15 @@ async fn ws_handler(mut socket: WebSocket, addr: SocketAddr, state: HandlerState
                                 store: store.clone(),
                                 tick_rx: &tick_rx,
                                 ticker_cmd_tx: &ticker_cmd_tx,
+                                ableton_cmd_tx: &ableton_cmd_tx,
                                 controller_cmd_tx: &controller_cmd_tx,
                                 communicator_cmd_tx: &communicator_cmd_tx,
                                 client_cmd_broadcast_tx: &client_cmd_broadcast_tx,
                                 ticker_state: &ticker_state,
+                                ableton_state: &ableton_state,
                                 controller_state: &controller_state,
                                 communicator_state: &communicator_state,
+                                monitor: &mut monitor,
                             }).await;
                         }
                         Message::Close(_) => {
@@ -105,12 +125,24 @@ async fn ws_handler(mut socket: WebSocket, addr: SocketAddr, state: HandlerState
             Ok(cmd) = client_cmd_broadcast_rx.recv() => {
                 respond(&mut socket, cmd).await;
             }
+            Some(entry) = async { monitor.as_mut().unwrap().recv().await }, if monitor.is_some() => {
+                respond(&mut socket, ClientCommand::CommMonitorEntry { entry }).await;
+            }
             Ok(()) = tick_rx.changed() => {
                 let maybe_tick = *tick_rx.borrow_and_update();
-                if let (Some(tick), max) = maybe_tick {
-                    respond(&mut socket, ClientCommand::TickerTick { tick, max }).await;
+                if let Tick { tick: Some(tick), max, bar, due, .. } = maybe_tick {
+                    pending_ticks.push_back((due, ClientCommand::TickerTick { tick, max, bar }));
+                } else {
+                    // stopped, ticks still ahead never play
+                    pending_ticks.clear();
                 }
             }
+            _ = sleep_until(pending_ticks.front().map_or_else(Instant::now, |(due, _)| *due)),
+                if !pending_ticks.is_empty() =>
+            {
+                let (_, cmd) = pending_ticks.pop_front().unwrap();
+                respond(&mut socket, cmd).await;
+            }
             Ok(()) = connection_status_rx.changed() => {
                 let established = *connection_status_rx.borrow_and_update();
                 respond(&mut socket, ClientCommand::CommStatusChanged {
@@ -138,19 +170,41 @@ async fn respond(socket: &mut WebSocket, cmd: ClientCommand) {
     }
 }
 
+/// Tells the client off if the tempo is the external clock's, whether it did
+async fn external_clock(socket: &mut WebSocket, ticker_state: &TickerState, summary: &str) -> bool {
+    let (ClockSource::Osc { path, .. } | ClockSource::Midi { path }) =
+        &*ticker_state.clock.read().await
+    else {
+        return false;
+    };
+    respond(
+        socket,
+        ClientCommand::Notify {
+            severity: Severity::Error,
+            summary: summary.to_string(),
+            detail: format!("Tempo follows the external clock on \"{}\"", path),
+        },
+    )
+    .await;
+    true
+}
+
 #[derive(Debug)]
 pub struct ProcessArg<'a> {
     cmd: ServerCommand,
     pub store: Store,
     socket: &'a mut WebSocket,
-    tick_rx: &'a watch::Receiver<(Option<usize>, usize)>,
+    tick_rx: &'a watch::Receiver<Tick>,
     ticker_cmd_tx: &'a mpsc::Sender<TickerCommand>,
+    ableton_cmd_tx: &'a mpsc::Sender<AbletonCommand>,
     controller_cmd_tx: &'a mpsc::Sender<ControllerCommand>,
     communicator_cmd_tx: &'a mpsc::Sender<CommunicatorCommand>,
     client_cmd_broadcast_tx: &'a broadcast::Sender<ClientCommand>,
     ticker_state: &'a TickerState,
+    ableton_state: &'a AbletonState,
     controller_state: &'a ControllerState,
     communicator_state: &'a CommunicatorState,
+    monitor: &'a mut Option<MonitorSubscription>, // this client's own
 }
 
 async fn process(arg: ProcessArg<'_>) {
@@ -160,12 +214,15 @@ async fn process(arg: ProcessArg<'_>) {
         socket,
         tick_rx,
         ticker_cmd_tx,
+        ableton_cmd_tx,
         controller_cmd_tx,
         communicator_cmd_tx,
         client_cmd_broadcast_tx,
         ticker_state,
+        ableton_state,
         controller_state,
         communicator_state,
+        monitor,
     } = arg;
 
     match cmd {
@@ -178,15 +235,37 @@ async fn process(arg: ProcessArg<'_>) {
             );
         }
         ServerCommand::CommChangeAddr { addr: new_addr } => {
+            if let Err(err) = communicator_state.sinks.parse(&new_addr) {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Change Address".to_string(),
+                        detail: format!("Address \"{}\" is invalid: {}", new_addr, err),
+                    },
+                )
+                .await;
+                return;
+            }
             communicator_cmd_tx
                 .send(CommunicatorCommand::ChangeTargetAddr {
+                    name: DEFAULT_TARGET_NAME.to_string(),
                     addr: new_addr.clone(),
                 })
                 .await
                 .unwrap();
             broadcast(
                 client_cmd_broadcast_tx,
-                ClientCommand::CommAddrChanged { addr: new_addr },
+                ClientCommand::CommAddrChanged {
+                    addr: new_addr.clone(),
+                },
+            );
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::CommTargetAddrChanged {
+                    name: DEFAULT_TARGET_NAME.to_string(),
+                    addr: new_addr,
+                },
             );
         }
         ServerCommand::CtrlChangeContext { context } => {
@@ -235,6 +314,479 @@ async fn process(arg: ProcessArg<'_>) {
                 );
             }
         }
+        // LYN: Target
+        ServerCommand::CommTargetAdd { name, addr } => {
+            if let Err(err) = communicator_state.sinks.parse(&addr) {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Add Target".to_string(),
+                        detail: format!("Address \"{}\" is invalid: {}", addr, err),
+                    },
+                )
+                .await;
+                return;
+            }
+            let target = Target::new(name.clone(), addr);
+            // NOTE: checked and claimed under one lock, the communicator only inserts
+            // after the link is up, which would let a second add of the name through
+            let taken = {
+                let mut targets = communicator_state.targets.write().await;
+                let taken = targets.contains_key(&name);
+                if !taken {
+                    targets.insert(name.clone(), target.clone());
+                }
+                taken
+            };
+            if taken {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Add Target".to_string(),
+                        detail: format!("Target with name \"{}\" already exists", name),
+                    },
+                )
+                .await;
+                return;
+            }
+            communicator_cmd_tx
+                .send(CommunicatorCommand::AddTarget {
+                    target: target.clone(),
+                })
+                .await
+                .unwrap();
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::CommTargetAdded { name, target },
+            );
+        }
+        ServerCommand::CommTargetDelete { name } => {
+            if name == DEFAULT_TARGET_NAME {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Delete Target".to_string(),
+                        detail: "The default target can not be deleted".to_string(),
+                    },
+                )
+                .await;
+                return;
+            }
+            if communicator_state.targets.read().await.get(&name).is_none() {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Delete Target".to_string(),
+                        detail: format!("Target with name \"{}\" does not exist", name),
+                    },
+                )
+                .await;
+                return;
+            }
+            communicator_cmd_tx
+                .send(CommunicatorCommand::DeleteTarget { name: name.clone() })
+                .await
+                .unwrap();
+            let mut routes = store.routes.write().await;
+            for route in routes.extract_if(.., |route| route.target == name) {
+                broadcast(
+                    client_cmd_broadcast_tx,
+                    ClientCommand::CommRouteDeleted { route },
+                );
+            }
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::CommTargetDeleted { name },
+            );
+        }
+        ServerCommand::CommTargetChangeAddr { name, addr } => {
+            if communicator_state.targets.read().await.get(&name).is_none() {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Change Address".to_string(),
+                        detail: format!("Target with name \"{}\" does not exist", name),
+                    },
+                )
+                .await;
+                return;
+            }
+            if let Err(err) = communicator_state.sinks.parse(&addr) {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Change Address".to_string(),
+                        detail: format!("Address \"{}\" is invalid: {}", addr, err),
+                    },
+                )
+                .await;
+                return;
+            }
+            communicator_cmd_tx
+                .send(CommunicatorCommand::ChangeTargetAddr {
+                    name: name.clone(),
+                    addr: addr.clone(),
+                })
+                .await
+                .unwrap();
+            if name == DEFAULT_TARGET_NAME {
+                broadcast(
+                    client_cmd_broadcast_tx,
+                    ClientCommand::CommAddrChanged { addr: addr.clone() },
+                );
+            }
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::CommTargetAddrChanged { name, addr },
+            );
+        }
+        ServerCommand::CommTargetSetTiming {
+            name,
+            lookahead_ms,
+            latency_ms,
+        } => {
+            if communicator_state.targets.read().await.get(&name).is_none() {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set Timing".to_string(),
+                        detail: format!("Target with name \"{}\" does not exist", name),
+                    },
+                )
+                .await;
+                return;
+            }
+            communicator_cmd_tx
+                .send(CommunicatorCommand::SetTargetTiming {
+                    name: name.clone(),
+                    lookahead_ms,
+                    latency_ms,
+                })
+                .await
+                .unwrap();
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::CommTargetTimingChanged {
+                    name,
+                    lookahead_ms,
+                    latency_ms,
+                },
+            );
+        }
+        ServerCommand::CommTargetSetFallbacks {
+            name,
+            fallbacks,
+            grace_ms,
+        } => {
+            if communicator_state.targets.read().await.get(&name).is_none() {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set Fallbacks".to_string(),
+                        detail: format!("Target with name \"{}\" does not exist", name),
+                    },
+                )
+                .await;
+                return;
+            }
+            if grace_ms < MIN_FAILOVER_GRACE_MS {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set Fallbacks".to_string(),
+                        detail: format!(
+                            "Grace period of {} ms is too short, expecting at least {} ms",
+                            grace_ms, MIN_FAILOVER_GRACE_MS
+                        ),
+                    },
+                )
+                .await;
+                return;
+            }
+            for addr in &fallbacks {
+                if let Err(err) = communicator_state.sinks.parse(addr) {
+                    respond(
+                        socket,
+                        ClientCommand::Notify {
+                            severity: Severity::Error,
+                            summary: "Failed to Set Fallbacks".to_string(),
+                            detail: format!("Address \"{}\" is invalid: {}", addr, err),
+                        },
+                    )
+                    .await;
+                    return;
+                }
+            }
+            communicator_cmd_tx
+                .send(CommunicatorCommand::SetTargetFallbacks {
+                    name: name.clone(),
+                    fallbacks: fallbacks.clone(),
+                    grace_ms,
+                })
+                .await
+                .unwrap();
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::CommTargetFallbacksChanged {
+                    name,
+                    fallbacks,
+                    grace_ms,
+                },
+            );
+        }
+        ServerCommand::CommTargetSetEcho {
+            name,
+            echo_path,
+            auto_latency,
+        } => {
+            if communicator_state.targets.read().await.get(&name).is_none() {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set Echo".to_string(),
+                        detail: format!("Target with name \"{}\" does not exist", name),
+                    },
+                )
+                .await;
+                return;
+            }
+            if let Some(path) = &echo_path
+                && !path.starts_with('/')
+            {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set Echo".to_string(),
+                        detail: format!("Echo path \"{}\" must start with \"/\"", path),
+                    },
+                )
+                .await;
+                return;
+            }
+            communicator_cmd_tx
+                .send(CommunicatorCommand::SetTargetEcho {
+                    name: name.clone(),
+                    echo_path: echo_path.clone(),
+                    auto_latency,
+                })
+                .await
+                .unwrap();
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::CommTargetEchoChanged {
+                    name,
+                    echo_path,
+                    auto_latency,
+                },
+            );
+        }
+        ServerCommand::CommTargetSetTransforms { name, transforms } => {
+            if communicator_state.targets.read().await.get(&name).is_none() {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set Transforms".to_string(),
+                        detail: format!("Target with name \"{}\" does not exist", name),
+                    },
+                )
+                .await;
+                return;
+            }
+            for (i, rule) in transforms.iter().enumerate() {
+                if let Err(err) = rule.validate() {
+                    respond(
+                        socket,
+                        ClientCommand::Notify {
+                            severity: Severity::Error,
+                            summary: "Failed to Set Transforms".to_string(),
+                            detail: format!("Rule #{} is invalid: {}", i + 1, err),
+                        },
+                    )
+                    .await;
+                    return;
+                }
+            }
+            communicator_cmd_tx
+                .send(CommunicatorCommand::SetTargetTransforms {
+                    name: name.clone(),
+                    transforms: transforms.clone(),
+                })
+                .await
+                .unwrap();
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::CommTargetTransformsChanged { name, transforms },
+            );
+        }
+        // LYN: Route
+        ServerCommand::CommRouteAdd { route } => {
+            if communicator_state
+                .targets
+                .read()
+                .await
+                .get(&route.target)
+                .is_none()
+            {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Add Route".to_string(),
+                        detail: format!("Target with name \"{}\" does not exist", route.target),
+                    },
+                )
+                .await;
+                return;
+            }
+            let mut routes = store.routes.write().await;
+            if routes.contains(&route) {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Add Route".to_string(),
+                        detail: format!(
+                            "Route \"{}\" to \"{}\" already exists",
+                            route.prefix, route.target
+                        ),
+                    },
+                )
+                .await;
+            } else {
+                routes.push(route.clone());
+                broadcast(
+                    client_cmd_broadcast_tx,
+                    ClientCommand::CommRouteAdded { route },
+                );
+            }
+        }
+        ServerCommand::CommRouteDelete { route } => {
+            let mut routes = store.routes.write().await;
+            if let Some(index) = routes.iter().position(|r| *r == route) {
+                routes.remove(index);
+                broadcast(
+                    client_cmd_broadcast_tx,
+                    ClientCommand::CommRouteDeleted { route },
+                );
+            } else {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Delete Route".to_string(),
+                        detail: format!(
+                            "Route \"{}\" to \"{}\" does not exist",
+                            route.prefix, route.target
+                        ),
+                    },
+                )
+                .await;
+            }
+        }
+        ServerCommand::CommSetDelivery { kind, policy } => {
+            store.delivery.write().await.set(kind, policy);
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::CommDeliveryUpdated { kind, policy },
+            );
+        }
+        ServerCommand::CommSetDmxMappings { mappings } => {
+            for (i, mapping) in mappings.iter().enumerate() {
+                if let Err(err) = mapping.validate() {
+                    respond(
+                        socket,
+                        ClientCommand::Notify {
+                            severity: Severity::Error,
+                            summary: "Failed to Set DMX Mappings".to_string(),
+                            detail: format!("Mapping #{} is invalid: {}", i + 1, err),
+                        },
+                    )
+                    .await;
+                    return;
+                }
+            }
+            *store.dmx.write().await = mappings.clone();
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::CommDmxMappingsUpdated { mappings },
+            );
+        }
+        ServerCommand::CommSetMidiMappings { mappings } => {
+            for (i, mapping) in mappings.iter().enumerate() {
+                if let Err(err) = mapping.validate() {
+                    respond(
+                        socket,
+                        ClientCommand::Notify {
+                            severity: Severity::Error,
+                            summary: "Failed to Set MIDI Mappings".to_string(),
+                            detail: format!("Mapping #{} is invalid: {}", i + 1, err),
+                        },
+                    )
+                    .await;
+                    return;
+                }
+            }
+            *store.midi.write().await = mappings.clone();
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::CommMidiMappingsUpdated { mappings },
+            );
+        }
+        ServerCommand::CommSetSinkOptions { scheme, options } => {
+            if let Err(err) = communicator_state.sinks.validate_options(&scheme, &options) {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set Sink Options".to_string(),
+                        detail: err.to_string(),
+                    },
+                )
+                .await;
+                return;
+            }
+            // taken up by each target of the scheme on its next connection
+            store
+                .sinks
+                .write()
+                .await
+                .insert(scheme.clone(), options.clone());
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::CommSinkOptionsUpdated { scheme, options },
+            );
+        }
+        ServerCommand::CommMonitorSubscribe { prefix } => {
+            if let Some(prefix) = &prefix
+                && !prefix.starts_with('/')
+            {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Subscribe to Monitor".to_string(),
+                        detail: format!("Path prefix \"{}\" must start with \"/\"", prefix),
+                    },
+                )
+                .await;
+                return;
+            }
+            // replaces any previous subscription, e.g. to change the filter
+            *monitor = Some(communicator_state.monitor.subscribe(prefix));
+        }
+        ServerCommand::CommMonitorUnsubscribe => *monitor = None,
         // LYN: Pattern
         ServerCommand::PatternAdd { name } => {
             let mut patterns = store.patterns.write().await;
@@ -269,14 +821,48 @@ async fn process(arg: ProcessArg<'_>) {
                     socket,
                     ClientCommand::Notify {
                         severity: Severity::Error,
-                        summary: "Failed to Delete Pattern".to_string(),
-                        detail: format!("Pattern with name \"{}\" does not exist", name),
+                        summary: "Failed to Delete Pattern".to_string(),
+                        detail: format!("Pattern with name \"{}\" does not exist", name),
+                    },
+                )
+                .await;
+            }
+        }
+        ServerCommand::PatternEdit { name, pattern } => {
+            if let Some(resolution) = pattern.resolution
+                && !(1..=MAX_RESOLUTION).contains(&resolution)
+            {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: format!("Failed to Edit Pattern {}", name),
+                        detail: format!(
+                            "Resolution {} is out of range, expecting 1 to {} steps per beat",
+                            resolution, MAX_RESOLUTION
+                        ),
+                    },
+                )
+                .await;
+                return;
+            }
+            let project_resolution = *store.resolution.read().await;
+            if !pattern.fits(project_resolution) {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: format!("Failed to Edit Pattern {}", name),
+                        detail: format!(
+                            "Resolution {} doesn't divide the project's {} steps per beat, tracks would skip steps",
+                            pattern.resolution(project_resolution),
+                            project_resolution
+                        ),
                     },
                 )
                 .await;
+                return;
             }
-        }
-        ServerCommand::PatternEdit { name, pattern } => {
             let mut patterns = store.patterns.write().await;
             if let Some(existing_pattern) = patterns.get_mut(&name) {
                 *existing_pattern = pattern.clone();
@@ -367,8 +953,13 @@ async fn process(arg: ProcessArg<'_>) {
                 track.active = active;
                 if force {
                     if active {
-                        let (tick, _) = *tick_rx.borrow();
-                        track.progress = tick.map(|val| val % 16);
+                        let Tick { tick, .. } = *tick_rx.borrow();
+                        let bar = store
+                            .time_signature
+                            .read()
+                            .await
+                            .bar_steps(*store.resolution.read().await);
+                        track.progress = tick.map(|val| val % bar);
                     } else {
                         track.progress = None;
                     }
@@ -495,9 +1086,13 @@ async fn process(arg: ProcessArg<'_>) {
             };
             communicator_cmd_tx
                 .send(CommunicatorCommand::SendMessage {
-                    msg: MinOscMessage {
-                        path: event.path.clone(),
-                        arg: event.payload.clone(),
+                    out: Outgoing {
+                        msg: MinOscMessage {
+                            path: event.path.clone(),
+                            args: event.payload.clone(),
+                        },
+                        source: MessageSource::Event(event.name.clone()),
+                        tick: None,
                     },
                 })
                 .await
@@ -565,33 +1160,52 @@ async fn process(arg: ProcessArg<'_>) {
             }
         }
         ServerCommand::SliderSetVal { name, val } => {
-            let mut sliders = store.sliders.write().await;
-            if let Some(slider) = sliders.get_mut(&name) {
-                slider.val = val;
-                broadcast(
-                    client_cmd_broadcast_tx,
-                    ClientCommand::SliderValSet { name, val },
-                );
-                communicator_cmd_tx
-                    .send(CommunicatorCommand::SendMessage {
-                        msg: MinOscMessage {
-                            path: slider.path.clone(),
-                            arg: MinOscArg::Float(val),
+            let path = {
+                let mut sliders = store.sliders.write().await;
+                let Some(slider) = sliders.get_mut(&name) else {
+                    drop(sliders);
+                    respond(
+                        socket,
+                        ClientCommand::Notify {
+                            severity: Severity::Error,
+                            summary: "Failed to Set Slider Value".to_string(),
+                            detail: format!("Slider with name \"{}\" does not exist", name),
                         },
-                    })
-                    .await
-                    .unwrap();
-            } else {
+                    )
+                    .await;
+                    return;
+                };
+                slider.val = val;
+                slider.path.clone()
+            };
+            // the controller coalesces fast drags before they reach targets and clients
+            controller_cmd_tx
+                .send(ControllerCommand::MoveSlider { name, path, val })
+                .await
+                .unwrap();
+        }
+        ServerCommand::SliderSetRate { rate_hz } => {
+            // zero is unlimited
+            if rate_hz != 0.0 && !(rate_hz.is_finite() && rate_hz >= MIN_SLIDER_RATE_HZ) {
                 respond(
                     socket,
                     ClientCommand::Notify {
                         severity: Severity::Error,
-                        summary: "Failed to Set Slider Value".to_string(),
-                        detail: format!("Slider with name \"{}\" does not exist", name),
+                        summary: "Failed to Set Slider Rate".to_string(),
+                        detail: format!(
+                            "Rate {} Hz is invalid, expecting at least {} Hz, or 0 for unlimited",
+                            rate_hz, MIN_SLIDER_RATE_HZ
+                        ),
                     },
                 )
                 .await;
+                return;
             }
+            *store.slider_rate_hz.write().await = rate_hz;
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::SliderRateUpdated { rate_hz },
+            );
         }
         // LYN: Ticker
         ServerCommand::TickerPlay => {
@@ -617,13 +1231,225 @@ async fn process(arg: ProcessArg<'_>) {
             }
         }
         ServerCommand::TickerSetBpm { bpm } => {
+            if external_clock(socket, ticker_state, "Failed to Set BPM").await {
+                return;
+            }
+            if !(MIN_BPM..=MAX_BPM).contains(&bpm) {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set BPM".to_string(),
+                        detail: format!(
+                            "BPM {} is out of range, expecting {} to {}",
+                            bpm, MIN_BPM, MAX_BPM
+                        ),
+                    },
+                )
+                .await;
+                return;
+            }
             ticker_cmd_tx
                 .send(TickerCommand::SetBPM { bpm })
                 .await
                 .unwrap();
             broadcast(
                 client_cmd_broadcast_tx,
-                ClientCommand::TickerBpmUpdated { bpm },
+                ClientCommand::TickerBpmUpdated {
+                    bpm,
+                    time_signature: *ticker_state.time_signature.read().await,
+                },
+            );
+        }
+        ServerCommand::TickerRamp { ramp } => {
+            if external_clock(socket, ticker_state, "Failed to Ramp Tempo").await {
+                return;
+            }
+            if let Err(err) = ramp.validate() {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Ramp Tempo".to_string(),
+                        detail: err.to_string(),
+                    },
+                )
+                .await;
+                return;
+            }
+            ticker_cmd_tx
+                .send(TickerCommand::Ramp { ramp })
+                .await
+                .unwrap();
+        }
+        ServerCommand::TickerTap => {
+            if external_clock(socket, ticker_state, "Failed to Tap Tempo").await {
+                return;
+            }
+            // timed here, before the ticker gets to it
+            ticker_cmd_tx
+                .send(TickerCommand::Tap { at: Instant::now() })
+                .await
+                .unwrap();
+        }
+        ServerCommand::TickerNudge { ms } => {
+            if external_clock(socket, ticker_state, "Failed to Nudge").await {
+                return;
+            }
+            if ms.abs() > MAX_NUDGE_MS {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Nudge".to_string(),
+                        detail: format!(
+                            "Nudge of {} ms is too large, expecting at most {} ms either way",
+                            ms, MAX_NUDGE_MS
+                        ),
+                    },
+                )
+                .await;
+                return;
+            }
+            ticker_cmd_tx
+                .send(TickerCommand::Nudge { ms })
+                .await
+                .unwrap();
+        }
+        ServerCommand::TickerSetClock { clock } => {
+            if let Err(err) = clock.validate() {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set Clock".to_string(),
+                        detail: err.to_string(),
+                    },
+                )
+                .await;
+                return;
+            }
+            *ticker_state.clock.write().await = clock.clone();
+            ticker_cmd_tx
+                .send(TickerCommand::ClockChanged)
+                .await
+                .unwrap();
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::TickerClockUpdated { clock },
+            );
+        }
+        ServerCommand::TickerSetMetronome { metronome } => {
+            if let Err(err) = metronome.validate() {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set Metronome".to_string(),
+                        detail: err.to_string(),
+                    },
+                )
+                .await;
+                return;
+            }
+            // clicks change from the next tick, a count-in from the next start
+            *ticker_state.metronome.write().await = metronome.clone();
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::TickerMetronomeUpdated { metronome },
+            );
+        }
+        ServerCommand::TickerSetLink { enabled } => {
+            // joining takes a moment, Link reports the session once it's in
+            ableton_cmd_tx
+                .send(AbletonCommand::Enable { enabled })
+                .await
+                .unwrap();
+        }
+        ServerCommand::TickerSetResolution { resolution } => {
+            if !(1..=MAX_RESOLUTION).contains(&resolution) {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set Resolution".to_string(),
+                        detail: format!(
+                            "Resolution {} is out of range, expecting 1 to {} steps per beat",
+                            resolution, MAX_RESOLUTION
+                        ),
+                    },
+                )
+                .await;
+                return;
+            }
+            if let Err(err) = ticker_state
+                .time_signature
+                .read()
+                .await
+                .validate(resolution)
+            {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set Resolution".to_string(),
+                        detail: err.to_string(),
+                    },
+                )
+                .await;
+                return;
+            }
+            if let Some(pattern) = store
+                .patterns
+                .read()
+                .await
+                .values()
+                .find(|pat| !pat.fits(resolution))
+            {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set Resolution".to_string(),
+                        detail: format!(
+                            "Pattern {} plays at {} steps per beat, which doesn't divide {}",
+                            pattern.name,
+                            pattern.resolution(resolution),
+                            resolution
+                        ),
+                    },
+                )
+                .await;
+                return;
+            }
+            // picked up by the ticker on its next tick
+            *ticker_state.resolution.write().await = resolution;
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::TickerResolutionUpdated { resolution },
+            );
+        }
+        ServerCommand::TickerSetTimeSignature { time_signature } => {
+            if let Err(err) = time_signature.validate(*ticker_state.resolution.read().await) {
+                respond(
+                    socket,
+                    ClientCommand::Notify {
+                        severity: Severity::Error,
+                        summary: "Failed to Set Time Signature".to_string(),
+                        detail: err.to_string(),
+                    },
+                )
+                .await;
+                return;
+            }
+            // like the resolution, the ticker wraps at the new bar from its next tick on
+            *ticker_state.time_signature.write().await = time_signature;
+            broadcast(
+                client_cmd_broadcast_tx,
+                ClientCommand::TickerBpmUpdated {
+                    bpm: *ticker_state.bpm.read().await,
+                    time_signature,
+                },
             );
         }
         // LYN: Request
@@ -636,6 +1462,24 @@ async fn process(arg: ProcessArg<'_>) {
             )
             .await;
         }
+        ServerCommand::RequestTickerTimeSignature => {
+            respond(
+                socket,
+                ClientCommand::ResponseTickerTimeSignature {
+                    time_signature: *ticker_state.time_signature.read().await,
+                },
+            )
+            .await;
+        }
+        ServerCommand::RequestTickerResolution => {
+            respond(
+                socket,
+                ClientCommand::ResponseTickerResolution {
+                    resolution: *ticker_state.resolution.read().await,
+                },
+            )
+            .await;
+        }
         ServerCommand::RequestTickerPlaying => {
             respond(
                 socket,
@@ -646,7 +1490,7 @@ async fn process(arg: ProcessArg<'_>) {
             .await;
         }
         ServerCommand::RequestTickerTick => {
-            let (tick, max) = *tick_rx.borrow();
+            let Tick { tick, max, .. } = *tick_rx.borrow();
             respond(
                 socket,
                 ClientCommand::ResponseTickerTick {
@@ -656,6 +1500,43 @@ async fn process(arg: ProcessArg<'_>) {
             )
             .await;
         }
+        ServerCommand::RequestTickerLateness => {
+            respond(
+                socket,
+                ClientCommand::ResponseTickerLateness {
+                    lateness: *ticker_state.lateness.read().await,
+                },
+            )
+            .await;
+        }
+        ServerCommand::RequestTickerClock => {
+            respond(
+                socket,
+                ClientCommand::ResponseTickerClock {
+                    clock: ticker_state.clock.read().await.clone(),
+                },
+            )
+            .await;
+        }
+        ServerCommand::RequestTickerMetronome => {
+            respond(
+                socket,
+                ClientCommand::ResponseTickerMetronome {
+                    metronome: ticker_state.metronome.read().await.clone(),
+                },
+            )
+            .await;
+        }
+        ServerCommand::RequestTickerLink => {
+            respond(
+                socket,
+                ClientCommand::ResponseTickerLink {
+                    enabled: *ableton_state.enabled.read().await,
+                    peers: *ableton_state.peers.read().await,
+                },
+            )
+            .await;
+        }
         ServerCommand::RequestProjectName => {
             respond(
                 socket,
@@ -669,7 +1550,13 @@ async fn process(arg: ProcessArg<'_>) {
             respond(
                 socket,
                 ClientCommand::ResponseCommAddr {
-                    addr: communicator_state.target_addr.read().await.clone(),
+                    addr: communicator_state
+                        .targets
+                        .read()
+                        .await
+                        .get(DEFAULT_TARGET_NAME)
+                        .map(|target| target.addr.clone())
+                        .unwrap_or_default(),
                 },
             )
             .await;
@@ -678,7 +1565,78 @@ async fn process(arg: ProcessArg<'_>) {
             respond(
                 socket,
                 ClientCommand::ResponseCommStatus {
-                    established: *communicator_state.connected.read().await,
+                    established: communicator_state
+                        .connected
+                        .read()
+                        .await
+                        .get(DEFAULT_TARGET_NAME)
+                        .copied()
+                        .unwrap_or(false),
+                },
+            )
+            .await;
+        }
+        ServerCommand::RequestCommTargets => {
+            respond(
+                socket,
+                ClientCommand::ResponseCommTargets {
+                    targets: communicator_state.targets.read().await.clone(),
+                    statuses: communicator_state.connected.read().await.clone(),
+                    actives: communicator_state.active.read().await.clone(),
+                },
+            )
+            .await;
+        }
+        ServerCommand::RequestCommRoutes => {
+            respond(
+                socket,
+                ClientCommand::ResponseCommRoutes {
+                    routes: communicator_state.routes.read().await.clone(),
+                },
+            )
+            .await;
+        }
+        ServerCommand::RequestCommDelivery => {
+            respond(
+                socket,
+                ClientCommand::ResponseCommDelivery {
+                    delivery: communicator_state.delivery.read().await.clone(),
+                },
+            )
+            .await;
+        }
+        ServerCommand::RequestCommQueues => {
+            respond(
+                socket,
+                ClientCommand::ResponseCommQueues {
+                    queues: communicator_state.queues.read().await.clone(),
+                },
+            )
+            .await;
+        }
+        ServerCommand::RequestCommDmxMappings => {
+            respond(
+                socket,
+                ClientCommand::ResponseCommDmxMappings {
+                    mappings: store.dmx.read().await.clone(),
+                },
+            )
+            .await;
+        }
+        ServerCommand::RequestCommMidiMappings => {
+            respond(
+                socket,
+                ClientCommand::ResponseCommMidiMappings {
+                    mappings: store.midi.read().await.clone(),
+                },
+            )
+            .await;
+        }
+        ServerCommand::RequestCommSinkOptions => {
+            respond(
+                socket,
+                ClientCommand::ResponseCommSinkOptions {
+                    options: store.sinks.read().await.clone(),
                 },
             )
             .await;
@@ -728,5 +1686,14 @@ async fn process(arg: ProcessArg<'_>) {
             )
             .await;
         }
+        ServerCommand::RequestSliderRate => {
+            respond(
+                socket,
+                ClientCommand::ResponseSliderRate {
+                    rate_hz: *store.slider_rate_hz.read().await,
+                },
+            )
+            .await;
+        }
     }
 }
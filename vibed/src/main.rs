@@ -8,27 +8,45 @@ use tokio::{
 use tracing::info;
 use tracing_subscriber::EnvFilter;
 
+use crate::ableton::{AbletonArg, AbletonState};
+use crate::bridge::{OscBridge, bridge_upgrader};
 use crate::build::print_built_info;
 use crate::command::ClientCommand;
 use crate::communicator::{CommunicatorArg, CommunicatorState};
 use crate::controller::{ControllerArg, ControllerState};
 use crate::handler::{HandlerState, ws_upgrader};
+use crate::sink::SinkRegistry;
 use crate::store::Store;
-use crate::ticker::{TickerArg, TickerState};
+use crate::ticker::{Tick, TickerArg, TickerState};
 
+mod ableton;
+mod artnet;
+mod bridge;
 mod build;
 mod command;
 mod communicator;
 mod controller;
+mod fudi;
 mod handler;
 mod models;
+mod monitor;
 mod mosc;
+mod rtpmidi;
+mod sink;
 mod store;
+mod throttle;
 mod ticker;
+mod transform;
 
 const VIBED_SERVER_ADDR: &str = "0.0.0.0:8000";
 const DEFAULT_BPM: f32 = 120.0;
+const MIN_BPM: f32 = 20.0;
+const MAX_BPM: f32 = 999.0;
+const DEFAULT_RESOLUTION: usize = 4; // sixteenth notes
+const DEFAULT_SLIDER_RATE_HZ: f32 = 30.0;
+const MIN_SLIDER_RATE_HZ: f32 = 1.0; // held back values go out within a second
 const DEFAULT_NAME: &str = "Unnamed";
+const DEFAULT_TARGET_NAME: &str = "default";
 const DEFAULT_TARGET_ADDR: &str = "127.0.0.1:8001";
 static DEFAULT_SAVE_PATH: &str = "./vibe-store.json";
 
@@ -41,7 +59,10 @@ async fn main() {
 
     let store = Store::load(DEFAULT_SAVE_PATH);
     let state = init_state(store.clone());
-    let router = Router::new().route("/", get(ws_upgrader)).with_state(state);
+    let router = Router::new()
+        .route("/", get(ws_upgrader))
+        .route("/osc/{channel}", get(bridge_upgrader))
+        .with_state(state);
     let listener = tokio::net::TcpListener::bind(VIBED_SERVER_ADDR)
         .await
         .unwrap();
@@ -58,23 +79,47 @@ async fn main() {
 
 fn init_state(store: Store) -> HandlerState {
     // LYN: States
+    let bridge = OscBridge::default();
     let controller_state = ControllerState {
         context: Arc::new(AsyncRwLock::new(None)),
     };
     let ticker_state = TickerState {
         patterns: store.patterns.clone(),
+        targets: store.targets.clone(),
         bpm: store.bpm.clone(),
+        resolution: store.resolution.clone(),
+        time_signature: store.time_signature.clone(),
         playing: Arc::new(AsyncRwLock::new(false)),
+        clock: store.clock.clone(),
+        metronome: store.metronome.clone(),
+        lateness: Default::default(),
+    };
+    let ableton_state = AbletonState {
+        enabled: store.link.clone(),
+        peers: Default::default(),
     };
     let communicator_state = CommunicatorState {
-        target_addr: store.target_addr.clone(),
-        connected: Arc::new(AsyncRwLock::new(false)),
+        targets: store.targets.clone(),
+        routes: store.routes.clone(),
+        delivery: store.delivery.clone(),
+        connected: Default::default(),
+        queues: Default::default(),
+        active: Default::default(),
+        sinks: SinkRegistry::new(
+            bridge.clone(),
+            store.dmx.clone(),
+            store.midi.clone(),
+            store.sinks.clone(),
+        ),
+        monitor: Default::default(),
     };
 
     // LYN: Channels
     let (ticker_cmd_tx, ticker_cmd_rx) = mpsc::channel(32);
-    let (tick_tx, tick_rx) = watch::channel((None, 0));
+    let (ableton_cmd_tx, ableton_cmd_rx) = mpsc::channel(32);
+    let (tick_tx, tick_rx) = watch::channel(Tick::stopped());
     let (communicator_cmd_tx, communicator_cmd_rx) = mpsc::channel(32);
+    let (inbound_tx, inbound_rx) = mpsc::channel(32);
     let (connection_status_tx, connection_status_rx) = watch::channel(false);
     let (controller_cmd_tx, controller_cmd_rx) = mpsc::channel(32);
     let (client_cmd_broadcast, _) = broadcast::channel::<ClientCommand>(64);
@@ -86,6 +131,20 @@ fn init_state(store: Store) -> HandlerState {
             cmd_rx: ticker_cmd_rx,
             tick_tx,
             controller_state: controller_state.clone(),
+            ableton_cmd_tx: ableton_cmd_tx.clone(),
+            client_cmd_broadcast_tx: client_cmd_broadcast.clone(),
+        },
+    ));
+
+    // LYN: Spawn Ableton Link
+    spawn(ableton::main(
+        ableton_state.clone(),
+        AbletonArg {
+            cmd_rx: ableton_cmd_rx,
+            ticker_cmd_tx: ticker_cmd_tx.clone(),
+            client_cmd_broadcast_tx: client_cmd_broadcast.clone(),
+            bpm: ticker_state.bpm.clone(),
+            playing: ticker_state.playing.clone(),
         },
     ));
 
@@ -94,7 +153,9 @@ fn init_state(store: Store) -> HandlerState {
         communicator_state.clone(),
         CommunicatorArg {
             cmd_rx: communicator_cmd_rx,
+            inbound_tx,
             connection_status_tx,
+            client_cmd_broadcast_tx: client_cmd_broadcast.clone(),
         },
     ));
 
@@ -105,6 +166,8 @@ fn init_state(store: Store) -> HandlerState {
             store: store.clone(),
             cmd_rx: controller_cmd_rx,
             tick_rx: tick_rx.clone(),
+            inbound_rx,
+            ticker_cmd_tx: ticker_cmd_tx.clone(),
             communicator_cmd_tx: communicator_cmd_tx.clone(),
             client_cmd_broadcast_tx: client_cmd_broadcast.clone(),
         },
@@ -116,12 +179,15 @@ fn init_state(store: Store) -> HandlerState {
         tick_rx,
         connection_status_rx,
         ticker_cmd_tx,
+        ableton_cmd_tx,
         controller_cmd_tx,
         communicator_cmd_tx,
         client_cmd_broadcast,
         ticker_state,
+        ableton_state,
         controller_state,
         communicator_state,
+        bridge,
     }
 }
 
@@ -1,15 +1,183 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, fmt, sync::Arc};
 
 use serde::{Deserialize, Serialize};
 
 use tokio::sync::RwLock as AsyncRwLock;
 
-use crate::mosc::{MinOscArg, MinOscMessage};
+use crate::{
+    mosc::{MinOscArg, MinOscMessage, one_or_many_args},
+    transform::TransformRule,
+};
 
 // LYN: Page
 
-const PAGE_SIZE: usize = 4;
-type Page<T> = [T; PAGE_SIZE];
+/// A beat's worth of steps, as many as the resolution the pattern plays at
+type Page<T> = Vec<T>;
+
+pub const MAX_RESOLUTION: usize = 16; // steps per beat
+
+// LYN: Time Signature
+
+const MAX_BAR_BEATS: usize = 32;
+
+/// Length of a bar, tracks wrap and start on bar boundaries. Tempo and resolution count
+/// quarter notes whatever the unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeSignature {
+    pub beats: usize,
+    pub unit: usize, // note value of a beat, 8 for eighth notes
+}
+
+#[derive(Debug)]
+pub enum TimeSignatureError {
+    BeatsOutOfRange(usize),
+    InvalidUnit(usize),
+    UnevenBar {
+        signature: TimeSignature,
+        resolution: usize,
+    },
+}
+
+impl fmt::Display for TimeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.beats, self.unit)
+    }
+}
+
+impl fmt::Display for TimeSignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeSignatureError::BeatsOutOfRange(beats) => write!(
+                f,
+                "{} beats per bar is out of range, expecting 1 to {}",
+                beats, MAX_BAR_BEATS
+            ),
+            TimeSignatureError::InvalidUnit(unit) => write!(
+                f,
+                "Beat unit {} is invalid, expecting 1, 2, 4, 8, 16 or 32",
+                unit
+            ),
+            TimeSignatureError::UnevenBar {
+                signature,
+                resolution,
+            } => write!(
+                f,
+                "A bar of {} doesn't fall on whole steps at {} steps per beat",
+                signature, resolution
+            ),
+        }
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self { beats: 4, unit: 4 }
+    }
+}
+
+impl TimeSignature {
+    pub fn validate(&self, resolution: usize) -> Result<(), TimeSignatureError> {
+        if !(1..=MAX_BAR_BEATS).contains(&self.beats) {
+            return Err(TimeSignatureError::BeatsOutOfRange(self.beats));
+        }
+        if !self.unit.is_power_of_two() || self.unit > 32 {
+            return Err(TimeSignatureError::InvalidUnit(self.unit));
+        }
+        if !(self.beats * resolution * 4).is_multiple_of(self.unit) {
+            return Err(TimeSignatureError::UnevenBar {
+                signature: *self,
+                resolution,
+            });
+        }
+        Ok(())
+    }
+
+    /// Quarter notes in a bar
+    pub fn bar_beats(&self) -> f64 {
+        self.beats as f64 * 4.0 / self.unit.max(1) as f64
+    }
+
+    /// Steps in a bar at `resolution` steps per quarter note
+    pub fn bar_steps(&self, resolution: usize) -> usize {
+        (self.beats * resolution * 4 / self.unit.max(1)).max(1)
+    }
+
+    /// Steps in one of its beats, rounded down
+    pub fn beat_steps(&self, resolution: usize) -> usize {
+        (self.bar_steps(resolution) / self.beats.max(1)).max(1)
+    }
+
+    /// Quarter notes in one of its beats
+    pub fn unit_beats(&self) -> f64 {
+        4.0 / self.unit.max(1) as f64
+    }
+}
+
+// LYN: Metronome
+
+const MAX_COUNT_IN_BARS: usize = 8;
+
+/// Clicks on every beat of the time signature, the first of each bar accented
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Metronome {
+    pub enabled: bool, // clicking while playing, count-ins click regardless
+    pub accent: MinOscMessage,
+    pub beat: MinOscMessage,
+    #[serde(default)]
+    pub count_in: usize, // bars clicked before playback from a stop starts
+}
+
+#[derive(Debug)]
+pub enum MetronomeError {
+    PathNotAbsolute(String),
+    CountInOutOfRange(usize),
+}
+
+impl fmt::Display for MetronomeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetronomeError::PathNotAbsolute(path) => {
+                write!(f, "Path \"{}\" must start with \"/\"", path)
+            }
+            MetronomeError::CountInOutOfRange(bars) => write!(
+                f,
+                "Count-in of {} bars is out of range, expecting 0 to {}",
+                bars, MAX_COUNT_IN_BARS
+            ),
+        }
+    }
+}
+
+impl Default for Metronome {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            accent: MinOscMessage {
+                path: "/metronome/accent".to_string(),
+                args: vec![],
+            },
+            beat: MinOscMessage {
+                path: "/metronome/beat".to_string(),
+                args: vec![],
+            },
+            count_in: 0,
+        }
+    }
+}
+
+impl Metronome {
+    pub fn validate(&self) -> Result<(), MetronomeError> {
+        for msg in [&self.accent, &self.beat] {
+            if !msg.path.starts_with('/') {
+                return Err(MetronomeError::PathNotAbsolute(msg.path.clone()));
+            }
+        }
+        if self.count_in > MAX_COUNT_IN_BARS {
+            return Err(MetronomeError::CountInOutOfRange(self.count_in));
+        }
+        Ok(())
+    }
+}
 
 // LYN: Pattern
 
@@ -17,6 +185,8 @@ type Page<T> = [T; PAGE_SIZE];
 pub struct Pattern {
     pub name: String,
     pub page_count: usize,
+    #[serde(default)]
+    pub resolution: Option<usize>, // steps per beat, the project's if none
     pub midi_path: String,
     pub midi_codes: Vec<Page<Option<u8>>>,
     pub messages: Vec<Messages>,
@@ -33,33 +203,50 @@ impl Pattern {
         Self {
             name,
             page_count: 0,
+            resolution: None,
             midi_path: String::from("/"),
             midi_codes: Vec::new(),
             messages: Vec::new(),
         }
     }
-    pub fn get_osc_messages(&self, tick: usize) -> Vec<MinOscMessage> {
-        let (page, index) = (tick / PAGE_SIZE, tick % PAGE_SIZE);
+    pub fn resolution(&self, project: usize) -> usize {
+        self.resolution.unwrap_or(project)
+    }
+    /// Whether each of its steps lands on the project's grid, for tracks to play them all
+    pub fn fits(&self, project: usize) -> bool {
+        project.is_multiple_of(self.resolution(project))
+    }
+    /// `tick` counts steps of `resolution` per beat, the one the pattern plays at
+    pub fn get_osc_messages(&self, tick: usize, resolution: usize) -> Vec<MinOscMessage> {
+        let (page, index) = (tick / resolution, tick % resolution);
         if page >= self.page_count {
             return vec![];
         }
 
         let mut ret = Vec::new();
-        if let Some(midi_code) = self.midi_codes[page][index] {
+        if let Some(midi_code) = self
+            .midi_codes
+            .get(page)
+            .and_then(|codes| codes.get(index).copied().flatten())
+        {
             ret.push(MinOscMessage {
                 path: self.midi_path.to_owned(),
-                arg: MinOscArg::Float(midi_code as f32),
+                args: vec![MinOscArg::Float(midi_code as f32)],
             });
         }
         for message in &self.messages {
-            if message.actives[page][index] {
+            if message
+                .actives
+                .get(page)
+                .is_some_and(|actives| actives.get(index) == Some(&true))
+            {
                 ret.push(message.payload.to_owned());
             }
         }
         ret
     }
-    fn tick_count(&self) -> usize {
-        self.page_count * PAGE_SIZE
+    fn tick_count(&self, resolution: usize) -> usize {
+        self.page_count * resolution
     }
 }
 
@@ -75,14 +262,14 @@ pub struct Track {
     pub color: Option<String>,
 }
 
-fn mod_beat(total_length: usize, beat: usize) -> usize {
-    if total_length >= 16 {
-        beat % 16
-    } else if total_length >= 8 {
-        beat % 8
-    } else {
-        beat % 4
-    }
+/// Position within the launch grid: a bar, or for tracks shorter than that the longest
+/// even division of the bar that fits, but never less than a beat (all in steps)
+fn mod_beat(total_length: usize, tick: usize, bar: usize, beat: usize) -> usize {
+    let snap = (beat..=total_length.min(bar).max(beat))
+        .rev()
+        .find(|len| bar.is_multiple_of(*len))
+        .unwrap_or(bar);
+    tick % snap
 }
 
 impl Track {
@@ -99,6 +286,8 @@ impl Track {
     pub async fn get_osc_messages_and_advance(
         &mut self,
         tick: usize,
+        resolution: usize, // the project's, `tick` and the progress count in it
+        signature: &TimeSignature,
         patterns_map: Arc<AsyncRwLock<HashMap<String, Pattern>>>,
     ) -> Vec<MinOscMessage> {
         let patterns_map = patterns_map.read().await;
@@ -107,11 +296,15 @@ impl Track {
             .iter()
             .filter_map(|name| patterns_map.get(name))
             .collect::<Vec<_>>();
-        let total_length = patterns.iter().map(|pat| pat.tick_count()).sum();
+        let total_length = patterns.iter().map(|pat| pat.tick_count(resolution)).sum();
+        let (bar, beat) = (
+            signature.bar_steps(resolution),
+            signature.beat_steps(resolution),
+        );
 
         if !self.active {
             if let Some(progress) = self.progress {
-                if mod_beat(total_length, progress) == 0 {
+                if mod_beat(total_length, progress, bar, beat) == 0 {
                     self.progress = None;
                     return vec![];
                 }
@@ -120,8 +313,8 @@ impl Track {
             }
         }
         if self.progress.is_none() {
-            if mod_beat(total_length, tick) == 0 {
-                self.progress = Some(mod_beat(total_length, tick));
+            if mod_beat(total_length, tick, bar, beat) == 0 {
+                self.progress = Some(mod_beat(total_length, tick, bar, beat));
             } else {
                 return vec![];
             }
@@ -131,10 +324,10 @@ impl Track {
             unreachable!()
         };
         let pat = patterns.iter().find(|pat| {
-            if progress < pat.tick_count() {
+            if progress < pat.tick_count(resolution) {
                 return true;
             }
-            progress -= pat.tick_count();
+            progress -= pat.tick_count(resolution);
             false
         });
 
@@ -150,7 +343,14 @@ impl Track {
         };
 
         if let Some(pat) = pat {
-            pat.get_osc_messages(progress)
+            // NOTE: a pattern's own resolution divides the project's (see `Pattern::fits`), so
+            // its steps are every few of the project's
+            let own = pat.resolution(resolution);
+            if progress * own % resolution == 0 {
+                pat.get_osc_messages(progress * own / resolution, own)
+            } else {
+                vec![]
+            }
         } else {
             vec![]
         }
@@ -164,7 +364,8 @@ pub struct Event {
     pub name: String,
     pub path: String,
     pub shortcut: Option<String>,
-    pub payload: MinOscArg,
+    #[serde(deserialize_with = "one_or_many_args")]
+    pub payload: Vec<MinOscArg>,
     pub color: Option<String>,
 }
 
@@ -174,7 +375,7 @@ impl Event {
             name,
             path: String::from("/"),
             shortcut: None,
-            payload: MinOscArg::default(),
+            payload: vec![MinOscArg::default()],
             color: None,
         }
     }
@@ -190,6 +391,8 @@ pub struct Slider {
     pub max: f32,
     pub min: f32,
     pub color: Option<String>,
+    #[serde(default)]
+    pub feedback: bool, // follow values the target sends back on `path`
 }
 
 impl Slider {
@@ -201,6 +404,183 @@ impl Slider {
             max: 1.0,
             min: 0.0,
             color: None,
+            feedback: false,
+        }
+    }
+}
+
+// LYN: Target
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Target {
+    pub name: String,
+    pub addr: String,
+    #[serde(default)]
+    pub lookahead_ms: u64, // how early tick messages are sent, as timetagged bundles
+    #[serde(default)]
+    pub latency_ms: u64, // added to the timetags
+    #[serde(default)]
+    pub fallbacks: Vec<String>, // tried in order while `addr` is unreachable
+    #[serde(default = "Target::default_failover_grace_ms")]
+    pub failover_grace_ms: u64, // how long an address may be down before moving on
+    #[serde(default)]
+    pub echo_path: Option<String>, // pinged every second to measure the round trip
+    #[serde(default)]
+    pub auto_latency: bool, // derive `latency_ms` from the measured round trip
+    #[serde(default)]
+    pub transforms: Vec<TransformRule>, // applied in order right before sending
+}
+
+impl Target {
+    pub fn new(name: String, addr: String) -> Self {
+        Self {
+            name,
+            addr,
+            lookahead_ms: 0,
+            latency_ms: 0,
+            fallbacks: Vec::new(),
+            failover_grace_ms: Self::default_failover_grace_ms(),
+            echo_path: None,
+            auto_latency: false,
+            transforms: Vec::new(),
+        }
+    }
+
+    fn default_failover_grace_ms() -> u64 {
+        2000
+    }
+}
+
+// LYN: Route
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Route {
+    pub prefix: String, // e.g. `/visual/*`, matches `/visual` and everything under it
+    pub target: String,
+}
+
+impl Route {
+    pub fn matches(&self, path: &str) -> bool {
+        path_matches(&self.prefix, path)
+    }
+}
+
+/// Whether `prefix` (e.g. `/visual/*` or `/visual`) covers `path`, on whole path segments
+pub fn path_matches(prefix: &str, path: &str) -> bool {
+    let prefix = prefix.trim_end_matches('*').trim_end_matches('/');
+    prefix.is_empty()
+        || path == prefix
+        || path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+// LYN: Delivery
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageKind {
+    Pattern,
+    Slider,
+    Event,
+}
+
+/// What produced an outgoing message, by name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "name", rename_all = "lowercase")]
+pub enum MessageSource {
+    Pattern(String),
+    Track(String),
+    Event(String),
+    Slider(String),
+    Metronome,
+}
+
+impl MessageSource {
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            MessageSource::Pattern(_) | MessageSource::Track(_) | MessageSource::Metronome => {
+                MessageKind::Pattern
+            }
+            MessageSource::Event(_) => MessageKind::Event,
+            MessageSource::Slider(_) => MessageKind::Slider,
         }
     }
 }
+
+/// What to do with a message while its target is unreachable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryPolicy {
+    Drop,
+    Latest, // keep only the latest value per path
+    Replay,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryPolicies {
+    pub pattern: DeliveryPolicy,
+    pub slider: DeliveryPolicy,
+    pub event: DeliveryPolicy,
+}
+
+impl Default for DeliveryPolicies {
+    fn default() -> Self {
+        Self {
+            pattern: DeliveryPolicy::Drop,
+            slider: DeliveryPolicy::Latest,
+            event: DeliveryPolicy::Replay,
+        }
+    }
+}
+
+impl DeliveryPolicies {
+    pub fn get(&self, kind: MessageKind) -> DeliveryPolicy {
+        match kind {
+            MessageKind::Pattern => self.pattern,
+            MessageKind::Slider => self.slider,
+            MessageKind::Event => self.event,
+        }
+    }
+    pub fn set(&mut self, kind: MessageKind, policy: DeliveryPolicy) {
+        match kind {
+            MessageKind::Pattern => self.pattern = policy,
+            MessageKind::Slider => self.slider = policy,
+            MessageKind::Event => self.event = policy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn four_four_launches_as_before() {
+        let signature = TimeSignature { beats: 4, unit: 4 };
+        let (bar, beat) = (signature.bar_steps(4), signature.beat_steps(4));
+        for len in 1..40 {
+            let snap = if len >= 16 {
+                16
+            } else if len >= 8 {
+                8
+            } else {
+                4
+            };
+            for tick in 0..32 {
+                assert_eq!(mod_beat(len, tick, bar, beat), tick % snap, "{} steps", len);
+            }
+        }
+    }
+
+    #[test]
+    fn short_tracks_snap_to_a_beat() {
+        // 7/8 at four steps per quarter note, a bar of 14 steps and beats of 2
+        let signature = TimeSignature { beats: 7, unit: 8 };
+        let (bar, beat) = (signature.bar_steps(4), signature.beat_steps(4));
+        assert_eq!((bar, beat), (14, 2));
+        assert_eq!(mod_beat(1, 3, bar, beat), 1);
+        assert_eq!(mod_beat(8, 9, bar, beat), 2); // 7 steps
+        assert_eq!(mod_beat(20, 15, bar, beat), 1);
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use crate::{
+    models::{MessageSource, path_matches},
+    mosc::MinOscMessage,
+};
+
+const MONITOR_CHANNEL_CAPACITY: usize = 1024;
+
+/// A message on its way to the targets, and what produced it
+#[derive(Debug, Clone)]
+pub struct Outgoing {
+    pub msg: MinOscMessage,
+    pub source: MessageSource,
+    pub tick: Option<usize>, // pattern and track messages only
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryOutcome {
+    Sent,
+    Queued, // held back until the target is reachable again
+    Dropped,
+}
+
+/// What happened to one outgoing message at one target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitorEntry {
+    pub time_ms: u64, // unix time
+    pub tick: Option<usize>,
+    pub source: MessageSource,
+    pub target: String,
+    pub msg: MinOscMessage,
+    pub outcome: DeliveryOutcome,
+}
+
+/// Live feed of delivery outcomes, for clients to see what actually went out
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    tx: broadcast::Sender<MonitorEntry>,
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(MONITOR_CHANNEL_CAPACITY).0,
+        }
+    }
+}
+
+impl Monitor {
+    pub fn record(&self, target: &str, out: &Outgoing, outcome: DeliveryOutcome) {
+        // don't bother cloning every message while nobody is watching
+        if self.tx.receiver_count() == 0 {
+            return;
+        }
+        let time_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_millis() as u64)
+            .unwrap_or_default();
+        let _ = self.tx.send(MonitorEntry {
+            time_ms,
+            tick: out.tick,
+            source: out.source.clone(),
+            target: target.to_string(),
+            msg: out.msg.clone(),
+            outcome,
+        });
+    }
+
+    /// Entries from now on, only those whose path is under `prefix` if given
+    pub fn subscribe(&self, prefix: Option<String>) -> MonitorSubscription {
+        MonitorSubscription {
+            rx: self.tx.subscribe(),
+            prefix,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MonitorSubscription {
+    rx: broadcast::Receiver<MonitorEntry>,
+    prefix: Option<String>,
+}
+
+impl MonitorSubscription {
+    pub async fn recv(&mut self) -> Option<MonitorEntry> {
+        loop {
+            match self.rx.recv().await {
+                Ok(entry) => {
+                    if self
+                        .prefix
+                        .as_ref()
+                        .is_none_or(|prefix| path_matches(prefix, &entry.msg.path))
+                    {
+                        return Some(entry);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Monitor subscriber lagged, skipped {} entries", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
@@ -1,17 +1,47 @@
-use rosc::OscMessage;
-use serde::{Deserialize, Serialize};
+use rosc::{OscMessage, OscPacket, OscType};
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "MinOscMessageRepr")]
 pub struct MinOscMessage {
     pub path: String,
-    pub arg: MinOscArg,
+    pub args: Vec<MinOscArg>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// NOTE: older project files carry exactly one `arg` instead of `args`
+#[derive(Deserialize)]
+struct MinOscMessageRepr {
+    path: String,
+    #[serde(default)]
+    args: Vec<MinOscArg>,
+    #[serde(default)]
+    arg: Option<MinOscArg>,
+}
+
+impl From<MinOscMessageRepr> for MinOscMessage {
+    fn from(val: MinOscMessageRepr) -> Self {
+        let mut args = val.args;
+        if let Some(arg) = val.arg {
+            args.insert(0, arg);
+        }
+        MinOscMessage {
+            path: val.path,
+            args,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", content = "value")]
 pub enum MinOscArg {
+    Int(i32),
     Float(f32),
     String(String),
+    Bool(bool),
+    Nil,
+    Blob(Vec<u8>),
+    Long(i64),
+    Double(f64),
 }
 
 impl Default for MinOscArg {
@@ -20,14 +50,117 @@ impl Default for MinOscArg {
     }
 }
 
+impl MinOscArg {
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            MinOscArg::Int(i) => Some(*i as f32),
+            MinOscArg::Float(f) => Some(*f),
+            MinOscArg::Long(l) => Some(*l as f32),
+            MinOscArg::Double(d) => Some(*d as f32),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            MinOscArg::Int(i) => Some(*i as f64),
+            MinOscArg::Float(f) => Some(*f as f64),
+            MinOscArg::Long(l) => Some(*l as f64),
+            MinOscArg::Double(d) => Some(*d),
+            _ => None,
+        }
+    }
+}
+
+/// Accepts either a single arg (older project files) or a list of them
+pub fn one_or_many_args<'de, D>(deserializer: D) -> Result<Vec<MinOscArg>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(MinOscArg),
+        Many(Vec<MinOscArg>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(arg) => vec![arg],
+        OneOrMany::Many(args) => args,
+    })
+}
+
+impl From<MinOscArg> for OscType {
+    fn from(val: MinOscArg) -> Self {
+        match val {
+            MinOscArg::Int(i) => OscType::Int(i),
+            MinOscArg::Float(f) => OscType::Float(f),
+            MinOscArg::String(s) => OscType::String(s),
+            MinOscArg::Bool(b) => OscType::Bool(b),
+            MinOscArg::Nil => OscType::Nil,
+            MinOscArg::Blob(b) => OscType::Blob(b),
+            MinOscArg::Long(l) => OscType::Long(l),
+            MinOscArg::Double(d) => OscType::Double(d),
+        }
+    }
+}
+
+impl TryFrom<OscType> for MinOscArg {
+    type Error = OscType;
+
+    fn try_from(val: OscType) -> Result<Self, Self::Error> {
+        Ok(match val {
+            OscType::Int(i) => MinOscArg::Int(i),
+            OscType::Float(f) => MinOscArg::Float(f),
+            OscType::String(s) => MinOscArg::String(s),
+            OscType::Bool(b) => MinOscArg::Bool(b),
+            OscType::Nil => MinOscArg::Nil,
+            OscType::Blob(b) => MinOscArg::Blob(b),
+            OscType::Long(l) => MinOscArg::Long(l),
+            OscType::Double(d) => MinOscArg::Double(d),
+            _ => return Err(val),
+        })
+    }
+}
+
 impl From<MinOscMessage> for OscMessage {
     fn from(val: MinOscMessage) -> Self {
         OscMessage {
             addr: val.path,
-            args: vec![match val.arg {
-                MinOscArg::Float(f) => rosc::OscType::Float(f),
-                MinOscArg::String(s) => rosc::OscType::String(s),
-            }],
+            args: val.args.into_iter().map(OscType::from).collect(),
         }
     }
 }
+
+impl TryFrom<OscMessage> for MinOscMessage {
+    type Error = OscMessage;
+
+    /// Fails on argument types we don't model (timetags, colors, arrays, ...)
+    fn try_from(val: OscMessage) -> Result<Self, Self::Error> {
+        let Ok(args) = val
+            .args
+            .iter()
+            .cloned()
+            .map(MinOscArg::try_from)
+            .collect::<Result<Vec<_>, _>>()
+        else {
+            return Err(val);
+        };
+        Ok(MinOscMessage {
+            path: val.addr,
+            args,
+        })
+    }
+}
+
+/// Flattens (nested) bundles into their messages, in order
+pub fn flatten_packet(packet: OscPacket) -> Vec<OscMessage> {
+    match packet {
+        OscPacket::Message(msg) => vec![msg],
+        OscPacket::Bundle(bundle) => bundle
+            .content
+            .into_iter()
+            .flat_map(flatten_packet)
+            .collect(),
+    }
+}
@@ -0,0 +1,527 @@
+use std::{
+    fmt,
+    hash::{BuildHasher, RandomState},
+    io,
+    net::SocketAddr,
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+use rosc::{OscPacket, OscType};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    net::{UdpSocket, lookup_host},
+    select,
+    sync::RwLock as AsyncRwLock,
+    time::{Instant, Interval, interval, sleep_until, timeout},
+};
+use tracing::{info, warn};
+
+use crate::{
+    mosc::{MinOscMessage, flatten_packet},
+    sink::{OutputSink, SinkConnector, with_default_port},
+};
+
+const RTPMIDI_PORT: u16 = 5004; // control port, data is the one after
+const SESSION_NAME: &str = "vibed";
+const APPLEMIDI_SIGNATURE: [u8; 2] = [0xff, 0xff];
+const APPLEMIDI_VERSION: u32 = 2;
+const INVITATION_TIMEOUT: Duration = Duration::from_secs(1);
+const INVITATION_ATTEMPTS: usize = 3;
+const PORT_PAIR_ATTEMPTS: usize = 8;
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+const SYNC_TIMEOUT: Duration = Duration::from_secs(35); // a few missed syncs
+const RTP_MIDI_PAYLOAD_TYPE: u8 = 0x61;
+const READ_BUF_SIZE: usize = 1024;
+
+// LYN: Mapping
+
+/// Messages on `path` become notes on `channel`, the first arg being the note number and the
+/// second, if any, the velocity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MidiMapping {
+    pub path: String,
+    pub channel: u8, // 1-16
+    #[serde(default = "MidiMapping::default_velocity")]
+    pub velocity: u8,
+    #[serde(default = "MidiMapping::default_length_ms")]
+    pub length_ms: u64, // until the note-off
+}
+
+#[derive(Debug)]
+pub enum MidiMappingError {
+    PathNotAbsolute(String),
+    ChannelOutOfRange(u8),
+    VelocityOutOfRange(u8),
+}
+
+impl fmt::Display for MidiMappingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MidiMappingError::PathNotAbsolute(path) => {
+                write!(f, "Path \"{}\" must start with \"/\"", path)
+            }
+            MidiMappingError::ChannelOutOfRange(channel) => {
+                write!(f, "Channel {} is out of range, expecting 1 to 16", channel)
+            }
+            MidiMappingError::VelocityOutOfRange(velocity) => {
+                write!(
+                    f,
+                    "Velocity {} is out of range, expecting 0 to 127",
+                    velocity
+                )
+            }
+        }
+    }
+}
+
+impl MidiMapping {
+    fn default_velocity() -> u8 {
+        100
+    }
+
+    fn default_length_ms() -> u64 {
+        100
+    }
+
+    pub fn validate(&self) -> Result<(), MidiMappingError> {
+        if !self.path.starts_with('/') {
+            return Err(MidiMappingError::PathNotAbsolute(self.path.clone()));
+        }
+        if !(1..=16).contains(&self.channel) {
+            return Err(MidiMappingError::ChannelOutOfRange(self.channel));
+        }
+        if self.velocity > 127 {
+            return Err(MidiMappingError::VelocityOutOfRange(self.velocity));
+        }
+        Ok(())
+    }
+}
+
+// LYN: Session
+
+/// AppleMIDI session commands, sent to both the control and the data port
+#[derive(Debug)]
+enum SessionCommand {
+    Invitation { token: u32 },
+    Accepted { token: u32, ssrc: u32 },
+    Rejected { token: u32 },
+    Sync { count: u8, timestamps: [u64; 3] },
+    End,
+}
+
+impl SessionCommand {
+    fn encode(&self, ssrc: u32) -> Vec<u8> {
+        let mut buf = APPLEMIDI_SIGNATURE.to_vec();
+        match self {
+            SessionCommand::Invitation { token } => {
+                buf.extend_from_slice(b"IN");
+                buf.extend_from_slice(&APPLEMIDI_VERSION.to_be_bytes());
+                buf.extend_from_slice(&token.to_be_bytes());
+                buf.extend_from_slice(&ssrc.to_be_bytes());
+                buf.extend_from_slice(SESSION_NAME.as_bytes());
+                buf.push(0);
+            }
+            SessionCommand::Sync { count, timestamps } => {
+                buf.extend_from_slice(b"CK");
+                buf.extend_from_slice(&ssrc.to_be_bytes());
+                buf.extend_from_slice(&[*count, 0, 0, 0]);
+                for timestamp in timestamps {
+                    buf.extend_from_slice(&timestamp.to_be_bytes());
+                }
+            }
+            SessionCommand::End => {
+                buf.extend_from_slice(b"BY");
+                buf.extend_from_slice(&APPLEMIDI_VERSION.to_be_bytes());
+                buf.extend_from_slice(&0u32.to_be_bytes());
+                buf.extend_from_slice(&ssrc.to_be_bytes());
+            }
+            // only ever received
+            SessionCommand::Accepted { .. } | SessionCommand::Rejected { .. } => {}
+        }
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        let word = |at: usize| Some(u32::from_be_bytes(buf.get(at..at + 4)?.try_into().ok()?));
+        let long = |at: usize| Some(u64::from_be_bytes(buf.get(at..at + 8)?.try_into().ok()?));
+        if buf.get(..2)? != APPLEMIDI_SIGNATURE {
+            return None;
+        }
+        Some(match buf.get(2..4)? {
+            b"IN" => SessionCommand::Invitation { token: word(8)? },
+            b"OK" => SessionCommand::Accepted {
+                token: word(8)?,
+                ssrc: word(12)?,
+            },
+            b"NO" => SessionCommand::Rejected { token: word(8)? },
+            b"CK" => SessionCommand::Sync {
+                count: *buf.get(8)?,
+                timestamps: [long(12)?, long(20)?, long(28)?],
+            },
+            b"BY" => SessionCommand::End,
+            _ => return None,
+        })
+    }
+}
+
+// LYN: Sink
+
+/// An RTP-MIDI (AppleMIDI) session, `rtpmidi://<host>[:port]`, we being the initiator
+pub struct RtpMidiConnector {
+    pub mappings: Arc<AsyncRwLock<Vec<MidiMapping>>>,
+}
+
+impl SinkConnector for RtpMidiConnector {
+    fn connect(&self, addr: String) -> BoxFuture<'static, io::Result<Box<dyn OutputSink>>> {
+        let mappings = self.mappings.clone();
+        Box::pin(async move {
+            let addr = with_default_port(&addr, RTPMIDI_PORT);
+            let Some(remote) = lookup_host(&addr).await?.next() else {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("could not resolve {}", addr),
+                ));
+            };
+            let (control, data) = bind_port_pair(remote).await?;
+            control.connect(remote).await?;
+            data.connect(SocketAddr::new(remote.ip(), remote.port() + 1))
+                .await?;
+
+            let hash = RandomState::new().hash_one(Instant::now());
+            let (token, ssrc) = (hash as u32, (hash >> 32) as u32);
+            invite(&control, token, ssrc).await?;
+            let remote_ssrc = invite(&data, token, ssrc).await?;
+            info!(
+                "Joined RTP-MIDI session at {} (ssrc {:08x})",
+                remote, remote_ssrc
+            );
+
+            Ok(Box::new(RtpMidiSink {
+                control,
+                data,
+                ssrc,
+                epoch: Instant::now(),
+                sequence: 0,
+                last_sync: Instant::now(),
+                sync_interval: interval(SYNC_INTERVAL), // first tick right away
+                mappings,
+                pending_offs: Vec::new(),
+                control_buf: vec![0u8; READ_BUF_SIZE],
+                data_buf: vec![0u8; READ_BUF_SIZE],
+            }) as Box<dyn OutputSink>)
+        })
+    }
+}
+
+/// Two sockets on consecutive ports, AppleMIDI peers expect data on the port after control
+async fn bind_port_pair(remote: SocketAddr) -> io::Result<(UdpSocket, UdpSocket)> {
+    let ip = if remote.is_ipv4() { "0.0.0.0" } else { "::" };
+    for _ in 0..PORT_PAIR_ATTEMPTS {
+        let control = UdpSocket::bind((ip, 0)).await?;
+        let port = control.local_addr()?.port();
+        if port == u16::MAX {
+            continue;
+        }
+        if let Ok(data) = UdpSocket::bind((ip, port + 1)).await {
+            return Ok((control, data));
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AddrInUse,
+        "no two consecutive local ports free",
+    ))
+}
+
+/// Invites the peer on one port, giving its ssrc once accepted
+async fn invite(socket: &UdpSocket, token: u32, ssrc: u32) -> io::Result<u32> {
+    let invitation = SessionCommand::Invitation { token }.encode(ssrc);
+    let mut buf = [0u8; READ_BUF_SIZE];
+    for _ in 0..INVITATION_ATTEMPTS {
+        socket.send(&invitation).await?;
+        let deadline = Instant::now() + INVITATION_TIMEOUT;
+        while let Ok(len) = timeout(
+            deadline.saturating_duration_since(Instant::now()),
+            socket.recv(&mut buf),
+        )
+        .await
+        {
+            match SessionCommand::decode(&buf[..len?]) {
+                Some(SessionCommand::Accepted { token: t, ssrc }) if t == token => {
+                    return Ok(ssrc);
+                }
+                Some(SessionCommand::Rejected { token: t }) if t == token => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        "invitation rejected",
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::TimedOut,
+        "invitation not answered",
+    ))
+}
+
+struct RtpMidiSink {
+    control: UdpSocket,
+    data: UdpSocket,
+    ssrc: u32,
+    epoch: Instant,
+    sequence: u16,
+    last_sync: Instant,
+    sync_interval: Interval,
+    mappings: Arc<AsyncRwLock<Vec<MidiMapping>>>,
+    pending_offs: Vec<(Instant, u8, u8)>, // when, status, note
+    control_buf: Vec<u8>,
+    data_buf: Vec<u8>,
+}
+
+impl RtpMidiSink {
+    /// Session clock, in the 100 µs units AppleMIDI uses
+    fn now(&self) -> u64 {
+        (self.epoch.elapsed().as_micros() / 100) as u64
+    }
+
+    fn sync(&self, count: u8, timestamps: [u64; 3]) -> Vec<u8> {
+        SessionCommand::Sync { count, timestamps }.encode(self.ssrc)
+    }
+
+    /// An RTP packet carrying the MIDI commands, without recovery journal
+    fn rtp_packet(&mut self, commands: &[[u8; 3]]) -> Vec<u8> {
+        self.sequence = self.sequence.wrapping_add(1);
+        let mut packet = vec![0x80, RTP_MIDI_PAYLOAD_TYPE];
+        packet.extend_from_slice(&self.sequence.to_be_bytes());
+        packet.extend_from_slice(&(self.now() as u32).to_be_bytes());
+        packet.extend_from_slice(&self.ssrc.to_be_bytes());
+
+        // every command but the first one is preceded by a zero delta time
+        let mut list = Vec::with_capacity(commands.len() * 4);
+        for (i, command) in commands.iter().enumerate() {
+            if i > 0 {
+                list.push(0);
+            }
+            list.extend_from_slice(command);
+        }
+        if list.len() <= 0x0f {
+            packet.push(list.len() as u8);
+        } else {
+            packet.push(0x80 | ((list.len() >> 8) as u8 & 0x0f));
+            packet.push(list.len() as u8);
+        }
+        packet.extend_from_slice(&list);
+        packet
+    }
+
+    fn next_off(&self) -> Option<Instant> {
+        self.pending_offs.iter().map(|(at, ..)| *at).min()
+    }
+}
+
+impl OutputSink for RtpMidiSink {
+    fn send<'a>(&'a mut self, packet: &'a OscPacket) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let mut commands = Vec::new();
+            {
+                let mappings = self.mappings.read().await;
+                for msg in flatten_packet(packet.clone()) {
+                    for mapping in mappings.iter().filter(|mapping| mapping.path == msg.addr) {
+                        let Some(note) = msg.args.first().and_then(as_midi_byte) else {
+                            continue;
+                        };
+                        let velocity = msg.args.get(1).and_then(as_midi_byte);
+                        let channel = mapping.channel - 1;
+                        // a retriggered note is ended first
+                        if let Some(i) = self
+                            .pending_offs
+                            .iter()
+                            .position(|(_, status, n)| *status == (0x80 | channel) && *n == note)
+                        {
+                            self.pending_offs.remove(i);
+                            commands.push([0x80 | channel, note, 0]);
+                        }
+                        commands.push([0x90 | channel, note, velocity.unwrap_or(mapping.velocity)]);
+                        self.pending_offs.push((
+                            Instant::now() + Duration::from_millis(mapping.length_ms),
+                            0x80 | channel,
+                            note,
+                        ));
+                    }
+                }
+            }
+            if commands.is_empty() {
+                return Ok(());
+            }
+            let packet = self.rtp_packet(&commands);
+            self.data.send(&packet).await.map(|_| ())
+        })
+    }
+
+    fn recv(&mut self) -> BoxFuture<'_, io::Result<Vec<MinOscMessage>>> {
+        Box::pin(async move {
+            loop {
+                let next_off = self.next_off();
+                select! {
+                    res = self.control.recv(&mut self.control_buf) => {
+                        // receiver feedback and such aside, only the end of the session matters
+                        if let Some(SessionCommand::End) = SessionCommand::decode(&self.control_buf[..res?]) {
+                            return Err(io::Error::new(
+                                io::ErrorKind::ConnectionAborted,
+                                "session ended by peer",
+                            ));
+                        }
+                    }
+                    res = self.data.recv(&mut self.data_buf) => {
+                        match SessionCommand::decode(&self.data_buf[..res?]) {
+                            Some(SessionCommand::Sync { count: 0, timestamps: [ts1, ..] }) => {
+                                let reply = self.sync(1, [ts1, self.now(), 0]);
+                                self.data.send(&reply).await?;
+                            }
+                            Some(SessionCommand::Sync { count: 1, timestamps: [ts1, ts2, _] }) => {
+                                let reply = self.sync(2, [ts1, ts2, self.now()]);
+                                self.data.send(&reply).await?;
+                                self.last_sync = Instant::now();
+                            }
+                            Some(SessionCommand::Sync { count: 2, .. }) => {
+                                self.last_sync = Instant::now();
+                            }
+                            Some(SessionCommand::End) => {
+                                return Err(io::Error::new(
+                                    io::ErrorKind::ConnectionAborted,
+                                    "session ended by peer",
+                                ));
+                            }
+                            _ => {}
+                        }
+                    }
+                    _ = self.sync_interval.tick() => {
+                        if self.last_sync.elapsed() > SYNC_TIMEOUT {
+                            return Err(io::Error::new(
+                                io::ErrorKind::TimedOut,
+                                "peer stopped answering clock sync",
+                            ));
+                        }
+                        let sync = self.sync(0, [self.now(), 0, 0]);
+                        self.data.send(&sync).await?;
+                    }
+                    _ = sleep_until(next_off.unwrap_or_else(Instant::now)), if next_off.is_some() =>
+                    {
+                        let now = Instant::now();
+                        let mut commands = Vec::new();
+                        self.pending_offs.retain(|(at, status, note)| {
+                            let due = *at <= now;
+                            if due {
+                                commands.push([*status, *note, 0]);
+                            }
+                            !due
+                        });
+                        let packet = self.rtp_packet(&commands);
+                        self.data.send(&packet).await?;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Notes start as soon as they arrive, RTP timestamps only order them
+    fn timetagged(&self) -> bool {
+        false
+    }
+}
+
+impl Drop for RtpMidiSink {
+    fn drop(&mut self) {
+        // no await in here, but UDP sends don't block anyway
+        if !self.pending_offs.is_empty() {
+            let offs = self
+                .pending_offs
+                .drain(..)
+                .map(|(_, status, note)| [status, note, 0])
+                .collect::<Vec<_>>();
+            let packet = self.rtp_packet(&offs);
+            let _ = self.data.try_send(&packet);
+        }
+        if let Err(err) = self
+            .control
+            .try_send(&SessionCommand::End.encode(self.ssrc))
+        {
+            warn!("Failed to end RTP-MIDI session: {}", err);
+        }
+    }
+}
+
+fn as_midi_byte(arg: &OscType) -> Option<u8> {
+    let val = match arg {
+        OscType::Int(i) => *i as f32,
+        OscType::Float(f) => f.round(),
+        OscType::Long(l) => *l as f32,
+        OscType::Double(d) => d.round() as f32,
+        _ => return None,
+    };
+    (0.0..=127.0).contains(&val).then_some(val as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invitation_round_trip() {
+        let encoded = SessionCommand::Invitation { token: 0x1234_5678 }.encode(0xcafe_f00d);
+        assert_eq!(&encoded[..4], b"\xff\xffIN");
+        assert_eq!(&encoded[4..8], &APPLEMIDI_VERSION.to_be_bytes());
+        assert_eq!(&encoded[12..16], &0xcafe_f00d_u32.to_be_bytes());
+        assert_eq!(&encoded[16..], b"vibed\0");
+        let Some(SessionCommand::Invitation { token }) = SessionCommand::decode(&encoded) else {
+            panic!("not an invitation");
+        };
+        assert_eq!(token, 0x1234_5678);
+    }
+
+    #[test]
+    fn decodes_accepted() {
+        let mut buf = b"\xff\xffOK".to_vec();
+        buf.extend_from_slice(&APPLEMIDI_VERSION.to_be_bytes());
+        buf.extend_from_slice(&7u32.to_be_bytes());
+        buf.extend_from_slice(&0xdead_beef_u32.to_be_bytes());
+        buf.extend_from_slice(b"peer\0");
+        let Some(SessionCommand::Accepted { token, ssrc }) = SessionCommand::decode(&buf) else {
+            panic!("not accepted");
+        };
+        assert_eq!((token, ssrc), (7, 0xdead_beef));
+
+        // cut short
+        assert!(SessionCommand::decode(&buf[..12]).is_none());
+    }
+
+    #[test]
+    fn sync_round_trip() {
+        let timestamps = [1, u64::MAX, 0x0102_0304_0506_0708];
+        let encoded = SessionCommand::Sync {
+            count: 1,
+            timestamps,
+        }
+        .encode(42);
+        assert_eq!(encoded.len(), 36);
+        let Some(SessionCommand::Sync {
+            count,
+            timestamps: decoded,
+        }) = SessionCommand::decode(&encoded)
+        else {
+            panic!("not a sync");
+        };
+        assert_eq!(count, 1);
+        assert_eq!(decoded, timestamps);
+    }
+
+    #[test]
+    fn rejects_foreign_packets() {
+        assert!(SessionCommand::decode(b"\x80\x61IN\0\0\0\x02").is_none());
+        assert!(SessionCommand::decode(b"\xff\xffXX\0\0\0\x02").is_none());
+        assert!(SessionCommand::decode(b"\xff").is_none());
+    }
+}
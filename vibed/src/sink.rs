@@ -0,0 +1,757 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+};
+
+use futures::future::BoxFuture;
+use rosc::{OscPacket, decoder::decode_udp, encoder::encode};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, UdpSocket, lookup_host},
+    sync::RwLock as AsyncRwLock,
+};
+use tracing::warn;
+
+use crate::{
+    artnet::{ArtNetConnector, DmxMapping},
+    bridge::{BridgeConnector, OscBridge},
+    fudi,
+    mosc::{MinOscMessage, flatten_packet},
+    rtpmidi::{MidiMapping, RtpMidiConnector},
+};
+
+// LYN: Output Sink
+
+/// Somewhere messages go, e.g. a TCP stream or browsers on the bridge
+///
+/// A sink counts as connected from the moment its [`SinkConnector`] hands it out until `send`
+/// or `recv` fails, after which the link connects a new one.
+pub trait OutputSink: Send {
+    /// Sends a single message or a (timetagged) bundle
+    fn send<'a>(&'a mut self, packet: &'a OscPacket) -> BoxFuture<'a, io::Result<()>>;
+
+    /// Waits for messages sent back, erroring once the sink is gone. Must be cancel safe.
+    fn recv(&mut self) -> BoxFuture<'_, io::Result<Vec<MinOscMessage>>>;
+
+    /// Whether the receiving end understands OSC bundles and their timetags
+    fn timetagged(&self) -> bool {
+        true
+    }
+
+    /// Whether the sink coming up says nothing about the receiving end being there, as with
+    /// UDP, where connecting always succeeds
+    fn connectionless(&self) -> bool {
+        false
+    }
+}
+
+impl fmt::Debug for dyn OutputSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OutputSink")
+    }
+}
+
+/// Opens sinks for one address scheme
+pub trait SinkConnector: Send + Sync {
+    /// Connects to `addr`, the part after `scheme://`
+    fn connect(&self, addr: String) -> BoxFuture<'static, io::Result<Box<dyn OutputSink>>>;
+}
+
+// LYN: Registry
+
+/// Connectors by address scheme, set up with the per-scheme [`SinkOptions`] of the project
+/// store on every connect
+#[derive(Clone)]
+pub struct SinkRegistry {
+    connectors: Arc<HashMap<&'static str, Arc<dyn SinkConnector>>>,
+    options: Arc<AsyncRwLock<HashMap<String, SinkOptions>>>,
+}
+
+impl fmt::Debug for SinkRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.connectors.keys()).finish()
+    }
+}
+
+impl SinkRegistry {
+    pub fn new(
+        bridge: OscBridge,
+        dmx: Arc<AsyncRwLock<Vec<DmxMapping>>>,
+        midi: Arc<AsyncRwLock<Vec<MidiMapping>>>,
+        options: Arc<AsyncRwLock<HashMap<String, SinkOptions>>>,
+    ) -> Self {
+        let mut connectors: HashMap<&'static str, Arc<dyn SinkConnector>> = HashMap::new();
+        connectors.insert("tcp", Arc::new(TcpConnector(Framing::Fudi)));
+        connectors.insert("tcp+fudi", Arc::new(TcpConnector(Framing::Fudi)));
+        connectors.insert("tcp+slip", Arc::new(TcpConnector(Framing::Slip)));
+        connectors.insert("tcp+len", Arc::new(TcpConnector(Framing::LengthPrefix)));
+        connectors.insert("tcp+text", Arc::new(TextConnector));
+        connectors.insert("udp", Arc::new(UdpConnector));
+        connectors.insert("ws", Arc::new(BridgeConnector { bridge }));
+        connectors.insert("artnet", Arc::new(ArtNetConnector { mappings: dmx }));
+        connectors.insert("rtpmidi", Arc::new(RtpMidiConnector { mappings: midi }));
+        Self {
+            connectors: Arc::new(connectors),
+            options,
+        }
+    }
+
+    fn known(&self) -> Vec<String> {
+        let mut known = self
+            .connectors
+            .keys()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        known.sort();
+        known
+    }
+
+    /// Parses an address, making sure some connector handles its scheme
+    pub fn parse(&self, addr: &str) -> Result<TargetAddr, TargetAddrParseError> {
+        let addr = addr.parse::<TargetAddr>()?;
+        if !self.connectors.contains_key(addr.scheme.as_str()) {
+            return Err(TargetAddrParseError::UnknownScheme {
+                scheme: addr.scheme,
+                known: self.known(),
+            });
+        }
+        Ok(addr)
+    }
+
+    pub fn validate_options(
+        &self,
+        scheme: &str,
+        options: &SinkOptions,
+    ) -> Result<(), SinkOptionsError> {
+        if !self.connectors.contains_key(scheme) {
+            return Err(SinkOptionsError::UnknownScheme {
+                scheme: scheme.to_string(),
+                known: self.known(),
+            });
+        }
+        match options.default_port {
+            Some(0) => Err(SinkOptionsError::ZeroPort),
+            // a channel name, not a host
+            Some(_) if scheme == "ws" => Err(SinkOptionsError::NoPorts(scheme.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn connect(
+        &self,
+        target: TargetAddr,
+    ) -> BoxFuture<'static, io::Result<Box<dyn OutputSink>>> {
+        let Some(connector) = self.connectors.get(target.scheme.as_str()).cloned() else {
+            return Box::pin(async move {
+                Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("no sink for scheme \"{}\"", target.scheme),
+                ))
+            });
+        };
+        let options = self.options.clone();
+        Box::pin(async move {
+            let options = options
+                .read()
+                .await
+                .get(&target.scheme)
+                .cloned()
+                .unwrap_or_default();
+            let addr = match options.default_port {
+                Some(port) => with_default_port(&target.addr, port),
+                None => target.addr,
+            };
+            let sink = connector.connect(addr).await?;
+            Ok(if options.bundles {
+                sink
+            } else {
+                Box::new(PlainSink(sink)) as Box<dyn OutputSink>
+            })
+        })
+    }
+}
+
+/// Settings for every target of one address scheme, kept in the project store and used
+/// from each target's next connection on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SinkOptions {
+    /// Port for addresses given without one, instead of the scheme's own
+    #[serde(default)]
+    pub default_port: Option<u16>,
+    /// Off sends plain messages on the tick, for receivers that can't take bundles
+    #[serde(default = "SinkOptions::default_bundles")]
+    pub bundles: bool,
+}
+
+impl Default for SinkOptions {
+    fn default() -> Self {
+        Self {
+            default_port: None,
+            bundles: Self::default_bundles(),
+        }
+    }
+}
+
+impl SinkOptions {
+    fn default_bundles() -> bool {
+        true
+    }
+}
+
+#[derive(Debug)]
+pub enum SinkOptionsError {
+    UnknownScheme { scheme: String, known: Vec<String> },
+    ZeroPort,
+    NoPorts(String),
+}
+
+impl fmt::Display for SinkOptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SinkOptionsError::UnknownScheme { scheme, known } => write!(
+                f,
+                "Unknown scheme \"{}\", expecting one of {}",
+                scheme,
+                known
+                    .iter()
+                    .map(|s| format!("\"{}\"", s))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            SinkOptionsError::ZeroPort => write!(f, "Port 0 can't be connected to"),
+            SinkOptionsError::NoPorts(scheme) => {
+                write!(f, "\"{}\" addresses have no port", scheme)
+            }
+        }
+    }
+}
+
+/// A sink with its bundle support turned off
+struct PlainSink(Box<dyn OutputSink>);
+
+impl OutputSink for PlainSink {
+    fn send<'a>(&'a mut self, packet: &'a OscPacket) -> BoxFuture<'a, io::Result<()>> {
+        self.0.send(packet)
+    }
+
+    fn recv(&mut self) -> BoxFuture<'_, io::Result<Vec<MinOscMessage>>> {
+        self.0.recv()
+    }
+
+    fn timetagged(&self) -> bool {
+        false
+    }
+
+    fn connectionless(&self) -> bool {
+        self.0.connectionless()
+    }
+}
+
+// LYN: Target Address
+
+/// Target address in the form of `[scheme://]addr`, defaults to FUDI over TCP without a scheme
+///
+/// Built-in schemes: `tcp` (FUDI), `tcp+slip`, `tcp+len`, `tcp+text`, `udp` and `ws`, the latter
+/// taking a bridge channel name instead of `host:port`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetAddr {
+    pub scheme: String,
+    pub addr: String,
+}
+
+#[derive(Debug)]
+pub enum TargetAddrParseError {
+    UnknownScheme { scheme: String, known: Vec<String> },
+    MissingAddr,
+}
+
+impl fmt::Display for TargetAddrParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetAddrParseError::UnknownScheme { scheme, known } => write!(
+                f,
+                "Unknown scheme \"{}\", expecting one of {}",
+                scheme,
+                known
+                    .iter()
+                    .map(|s| format!("\"{}\"", s))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            TargetAddrParseError::MissingAddr => write!(f, "Missing host and port"),
+        }
+    }
+}
+
+impl FromStr for TargetAddr {
+    type Err = TargetAddrParseError;
+
+    /// Only splits the scheme off, see [`SinkRegistry::parse`] for the validation
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, addr) = s.trim().split_once("://").unwrap_or(("tcp", s.trim()));
+        if addr.is_empty() {
+            return Err(TargetAddrParseError::MissingAddr);
+        }
+        Ok(TargetAddr {
+            scheme: scheme.to_ascii_lowercase(),
+            addr: addr.to_string(),
+        })
+    }
+}
+
+// LYN: TCP
+
+/// How OSC packets are delimited on a TCP stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// OSC packet bytes as FUDI decimal lists, the way Pd's `[netreceive]` + `[oscparse]` expects it
+    Fudi,
+    /// OSC 1.1 double-END SLIP framing (RFC 1055)
+    Slip,
+    /// OSC 1.0 framing, each packet prefixed by its size as a big-endian `int32`
+    LengthPrefix,
+}
+
+impl Framing {
+    pub fn frame(&self, packet: &[u8]) -> Vec<u8> {
+        match self {
+            Framing::Fudi => {
+                let packet = packet
+                    .iter()
+                    .map(|b| b.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    + ";";
+                packet.into_bytes()
+            }
+            Framing::Slip => {
+                let mut ret = Vec::with_capacity(packet.len() + 2);
+                ret.push(SLIP_END);
+                for &b in packet {
+                    match b {
+                        SLIP_END => ret.extend([SLIP_ESC, SLIP_ESC_END]),
+                        SLIP_ESC => ret.extend([SLIP_ESC, SLIP_ESC_ESC]),
+                        _ => ret.push(b),
+                    }
+                }
+                ret.push(SLIP_END);
+                ret
+            }
+            Framing::LengthPrefix => {
+                let mut ret = Vec::with_capacity(packet.len() + 4);
+                ret.extend((packet.len() as u32).to_be_bytes());
+                ret.extend(packet);
+                ret
+            }
+        }
+    }
+}
+
+const SLIP_END: u8 = 0xC0;
+const SLIP_ESC: u8 = 0xDB;
+const SLIP_ESC_END: u8 = 0xDC;
+const SLIP_ESC_ESC: u8 = 0xDD;
+
+/// Largest inbound frame we buffer, past it the peer is taken as broken and dropped
+pub const MAX_FRAME_SIZE: usize = 1 << 20;
+
+pub fn frame_too_large(len: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "inbound frame of {} bytes exceeds {} bytes",
+            len, MAX_FRAME_SIZE
+        ),
+    )
+}
+
+/// Splits an inbound byte stream back into OSC packets, the reverse of [`Framing::frame`]
+#[derive(Debug)]
+struct Deframer {
+    framing: Framing,
+    buf: Vec<u8>,
+}
+
+impl Deframer {
+    fn new(framing: Framing) -> Self {
+        Self {
+            framing,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Errors once a frame grows past [`MAX_FRAME_SIZE`]
+    fn push(&mut self, bytes: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+        self.buf.extend_from_slice(bytes);
+        let mut packets = Vec::new();
+        match self.framing {
+            Framing::Fudi => {
+                while let Some(end) = self.buf.iter().position(|&b| b == b';') {
+                    let msg = self.buf.drain(..=end).collect::<Vec<_>>();
+                    let msg = String::from_utf8_lossy(&msg[..end]);
+                    match msg
+                        .split_whitespace()
+                        .map(str::parse::<u8>)
+                        .collect::<Result<Vec<_>, _>>()
+                    {
+                        Ok(packet) if packet.is_empty() => {}
+                        Ok(packet) => packets.push(packet),
+                        Err(_) => warn!("Malformed FUDI byte list, ignoring: {}", msg),
+                    }
+                }
+                // whatever is left is a message still missing its `;`
+                if self.buf.len() > MAX_FRAME_SIZE {
+                    return Err(frame_too_large(self.buf.len()));
+                }
+            }
+            Framing::Slip => {
+                while let Some(end) = self.buf.iter().position(|&b| b == SLIP_END) {
+                    let frame = self.buf.drain(..=end).collect::<Vec<_>>();
+                    let mut packet = Vec::with_capacity(end);
+                    let mut escaped = false;
+                    for &b in &frame[..end] {
+                        if escaped {
+                            packet.push(match b {
+                                SLIP_ESC_END => SLIP_END,
+                                SLIP_ESC_ESC => SLIP_ESC,
+                                b => b,
+                            });
+                            escaped = false;
+                        } else if b == SLIP_ESC {
+                            escaped = true;
+                        } else {
+                            packet.push(b);
+                        }
+                    }
+                    // double-END framing leaves empty frames in between
+                    if !packet.is_empty() {
+                        packets.push(packet);
+                    }
+                }
+                if self.buf.len() > MAX_FRAME_SIZE {
+                    return Err(frame_too_large(self.buf.len()));
+                }
+            }
+            Framing::LengthPrefix => {
+                while self.buf.len() >= 4 {
+                    let len =
+                        u32::from_be_bytes([self.buf[0], self.buf[1], self.buf[2], self.buf[3]]);
+                    let len = len as usize;
+                    if len > MAX_FRAME_SIZE {
+                        return Err(frame_too_large(len));
+                    }
+                    if self.buf.len() < 4 + len {
+                        break;
+                    }
+                    packets.push(self.buf[4..4 + len].to_vec());
+                    self.buf.drain(..4 + len);
+                }
+            }
+        }
+        Ok(packets)
+    }
+}
+
+/// OSC over TCP with the given framing
+struct TcpConnector(Framing);
+
+impl SinkConnector for TcpConnector {
+    fn connect(&self, addr: String) -> BoxFuture<'static, io::Result<Box<dyn OutputSink>>> {
+        let framing = self.0;
+        Box::pin(async move {
+            Ok(Box::new(TcpSink {
+                stream: TcpStream::connect(addr).await?,
+                framing,
+                deframer: Deframer::new(framing),
+                buf: vec![0u8; READ_BUF_SIZE],
+            }) as Box<dyn OutputSink>)
+        })
+    }
+}
+
+const READ_BUF_SIZE: usize = 65536;
+
+struct TcpSink {
+    stream: TcpStream,
+    framing: Framing,
+    deframer: Deframer,
+    buf: Vec<u8>,
+}
+
+impl OutputSink for TcpSink {
+    fn send<'a>(&'a mut self, packet: &'a OscPacket) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            // FIXME: handle invalid osc message error
+            let packet = encode(packet).unwrap();
+            self.stream.write_all(&self.framing.frame(&packet)).await
+        })
+    }
+
+    fn recv(&mut self) -> BoxFuture<'_, io::Result<Vec<MinOscMessage>>> {
+        Box::pin(async move {
+            loop {
+                let len = read_stream(&mut self.stream, &mut self.buf).await?;
+                let msgs = self
+                    .deframer
+                    .push(&self.buf[..len])?
+                    .iter()
+                    .flat_map(|packet| decode_packet(packet))
+                    .collect::<Vec<_>>();
+                if !msgs.is_empty() {
+                    return Ok(msgs);
+                }
+            }
+        })
+    }
+}
+
+async fn read_stream(stream: &mut TcpStream, buf: &mut [u8]) -> io::Result<usize> {
+    match stream.read(buf).await? {
+        0 => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "connection closed by peer",
+        )),
+        len => Ok(len),
+    }
+}
+
+/// Decodes a binary OSC packet into the messages we can handle
+fn decode_packet(packet: &[u8]) -> Vec<MinOscMessage> {
+    let packet = match decode_udp(packet) {
+        Ok((_, packet)) => packet,
+        Err(err) => {
+            warn!("Failed to decode inbound osc packet: {:?}", err);
+            return Vec::new();
+        }
+    };
+    flatten_packet(packet)
+        .into_iter()
+        .filter_map(|msg| match MinOscMessage::try_from(msg) {
+            Ok(msg) => Some(msg),
+            Err(msg) => {
+                warn!("Unsupported inbound osc message, ignoring: {:?}", msg);
+                None
+            }
+        })
+        .collect()
+}
+
+// LYN: Plain FUDI
+
+/// Plain FUDI text messages over TCP, no OSC involved, e.g. `drums/kick 64;`
+struct TextConnector;
+
+impl SinkConnector for TextConnector {
+    fn connect(&self, addr: String) -> BoxFuture<'static, io::Result<Box<dyn OutputSink>>> {
+        Box::pin(async move {
+            Ok(Box::new(TextSink {
+                stream: TcpStream::connect(addr).await?,
+                deframer: fudi::Deframer::default(),
+                buf: vec![0u8; READ_BUF_SIZE],
+            }) as Box<dyn OutputSink>)
+        })
+    }
+}
+
+struct TextSink {
+    stream: TcpStream,
+    deframer: fudi::Deframer,
+    buf: Vec<u8>,
+}
+
+impl OutputSink for TextSink {
+    fn send<'a>(&'a mut self, packet: &'a OscPacket) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            for msg in flatten_packet(packet.clone()) {
+                match MinOscMessage::try_from(msg) {
+                    Ok(msg) => self.stream.write_all(&fudi::encode(&msg)).await?,
+                    Err(msg) => warn!("Message not representable in FUDI, ignoring: {:?}", msg),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn recv(&mut self) -> BoxFuture<'_, io::Result<Vec<MinOscMessage>>> {
+        Box::pin(async move {
+            loop {
+                let len = read_stream(&mut self.stream, &mut self.buf).await?;
+                let msgs = self.deframer.push(&self.buf[..len])?;
+                if !msgs.is_empty() {
+                    return Ok(msgs);
+                }
+            }
+        })
+    }
+
+    fn timetagged(&self) -> bool {
+        false
+    }
+}
+
+// LYN: UDP
+
+/// Plain binary OSC packets, one per datagram
+struct UdpConnector;
+
+impl SinkConnector for UdpConnector {
+    fn connect(&self, addr: String) -> BoxFuture<'static, io::Result<Box<dyn OutputSink>>> {
+        Box::pin(async move {
+            Ok(Box::new(UdpSink {
+                socket: connect_udp(&addr).await?,
+                buf: vec![0u8; READ_BUF_SIZE],
+            }) as Box<dyn OutputSink>)
+        })
+    }
+}
+
+/// Appends `port` to an address that doesn't have one, bare IPv6 hosts (`fe80::1`) included
+pub fn with_default_port(addr: &str, port: u16) -> String {
+    if addr.parse::<SocketAddr>().is_ok() {
+        return addr.to_string();
+    }
+    if let Ok(ip) = addr.parse::<IpAddr>() {
+        return SocketAddr::new(ip, port).to_string();
+    }
+    match addr.rsplit_once(':') {
+        // `host:port` or `[v6]:port`
+        Some((_, tail)) if tail.parse::<u16>().is_ok() => addr.to_string(),
+        _ => format!("{}:{}", addr, port),
+    }
+}
+
+// NOTE: UDP is connectionless, "connected" here means the socket is bound and the kernel
+// hasn't reported the target port as unreachable (ICMP) for datagrams we've sent.
+pub async fn connect_udp(addr: &str) -> io::Result<UdpSocket> {
+    let Some(remote) = lookup_host(addr).await?.next() else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("could not resolve {}", addr),
+        ));
+    };
+    let local = if remote.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(remote).await?;
+    Ok(socket)
+}
+
+struct UdpSink {
+    socket: UdpSocket,
+    buf: Vec<u8>,
+}
+
+impl OutputSink for UdpSink {
+    fn send<'a>(&'a mut self, packet: &'a OscPacket) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            // FIXME: handle invalid osc message error
+            let packet = encode(packet).unwrap();
+            self.socket.send(&packet).await.map(|_| ())
+        })
+    }
+
+    fn recv(&mut self) -> BoxFuture<'_, io::Result<Vec<MinOscMessage>>> {
+        Box::pin(async move {
+            loop {
+                // ICMP port unreachable surfaces as a `ConnectionRefused` here
+                let len = self.socket.recv(&mut self.buf).await?;
+                let msgs = decode_packet(&self.buf[..len]);
+                if !msgs.is_empty() {
+                    return Ok(msgs);
+                }
+            }
+        })
+    }
+
+    fn connectionless(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slip_round_trip() {
+        let packet = vec![1, SLIP_END, 2, SLIP_ESC, 3, SLIP_ESC_END, SLIP_ESC_ESC];
+        let framed = Framing::Slip.frame(&packet);
+        assert_eq!(framed.first(), Some(&SLIP_END));
+        assert_eq!(framed.last(), Some(&SLIP_END));
+        assert_eq!(
+            framed[1..framed.len() - 1]
+                .iter()
+                .filter(|&&b| b == SLIP_END)
+                .count(),
+            0
+        );
+
+        let mut deframer = Deframer::new(Framing::Slip);
+        assert_eq!(deframer.push(&framed).unwrap(), vec![packet]);
+    }
+
+    #[test]
+    fn slip_across_reads() {
+        let (a, b) = (vec![SLIP_END, 7, 8], vec![SLIP_ESC, 9]);
+        let stream = [Framing::Slip.frame(&a), Framing::Slip.frame(&b)].concat();
+        let mut deframer = Deframer::new(Framing::Slip);
+        let (head, tail) = stream.split_at(4);
+        assert_eq!(deframer.push(head).unwrap(), Vec::<Vec<u8>>::new());
+        assert_eq!(deframer.push(tail).unwrap(), vec![a, b]);
+    }
+
+    #[test]
+    fn length_prefix_round_trip() {
+        let packets = [vec![1, 2, 3], vec![], vec![4; 300]];
+        let stream = packets
+            .iter()
+            .flat_map(|p| Framing::LengthPrefix.frame(p))
+            .collect::<Vec<_>>();
+        let mut deframer = Deframer::new(Framing::LengthPrefix);
+        let mut got = Vec::new();
+        for chunk in stream.chunks(5) {
+            got.extend(deframer.push(chunk).unwrap());
+        }
+        assert_eq!(got, packets);
+    }
+
+    #[test]
+    fn fudi_bytes_round_trip() {
+        let packet = vec![0, 47, 255, 59];
+        let framed = Framing::Fudi.frame(&packet);
+        assert_eq!(framed, b"0 47 255 59;");
+        let mut deframer = Deframer::new(Framing::Fudi);
+        assert_eq!(deframer.push(&framed).unwrap(), vec![packet]);
+    }
+
+    #[test]
+    fn oversized_frames_are_refused() {
+        let mut deframer = Deframer::new(Framing::LengthPrefix);
+        assert!(deframer.push(&u32::MAX.to_be_bytes()).is_err());
+
+        let mut deframer = Deframer::new(Framing::Slip);
+        assert!(deframer.push(&vec![1; MAX_FRAME_SIZE + 1]).is_err());
+    }
+
+    #[test]
+    fn default_port() {
+        for (addr, expected) in [
+            ("10.0.0.2", "10.0.0.2:6454"),
+            ("10.0.0.2:99", "10.0.0.2:99"),
+            ("lights.local", "lights.local:6454"),
+            ("lights.local:99", "lights.local:99"),
+            ("fe80::1", "[fe80::1]:6454"),
+            ("[fe80::1]", "[fe80::1]:6454"),
+            ("[fe80::1]:99", "[fe80::1]:99"),
+        ] {
+            assert_eq!(with_default_port(addr, 6454), expected, "{}", addr);
+        }
+    }
+}
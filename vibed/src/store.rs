@@ -4,15 +4,33 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock as AsyncRwLock;
 
 use crate::{
-    DEFAULT_BPM, DEFAULT_NAME, DEFAULT_TARGET_ADDR,
-    models::{Event, Pattern, Slider, Track},
+    DEFAULT_BPM, DEFAULT_NAME, DEFAULT_RESOLUTION, DEFAULT_SLIDER_RATE_HZ, DEFAULT_TARGET_ADDR,
+    DEFAULT_TARGET_NAME,
+    artnet::DmxMapping,
+    models::{
+        DeliveryPolicies, Event, Metronome, Pattern, Route, Slider, Target, TimeSignature, Track,
+    },
+    rtpmidi::MidiMapping,
+    sink::SinkOptions,
+    ticker::ClockSource,
 };
 
 #[derive(Debug, Clone)]
 pub struct Store {
     pub name: Arc<AsyncRwLock<String>>,
     pub bpm: Arc<AsyncRwLock<f32>>,
-    pub target_addr: Arc<AsyncRwLock<String>>,
+    pub resolution: Arc<AsyncRwLock<usize>>, // steps per beat
+    pub time_signature: Arc<AsyncRwLock<TimeSignature>>,
+    pub targets: Arc<AsyncRwLock<HashMap<String, Target>>>,
+    pub routes: Arc<AsyncRwLock<Vec<Route>>>,
+    pub delivery: Arc<AsyncRwLock<DeliveryPolicies>>,
+    pub slider_rate_hz: Arc<AsyncRwLock<f32>>, // max rate slider moves go out at, 0 for unlimited
+    pub dmx: Arc<AsyncRwLock<Vec<DmxMapping>>>, // for `artnet://` targets
+    pub midi: Arc<AsyncRwLock<Vec<MidiMapping>>>, // for `rtpmidi://` targets
+    pub sinks: Arc<AsyncRwLock<HashMap<String, SinkOptions>>>, // by address scheme
+    pub link: Arc<AsyncRwLock<bool>>,          // whether to join Ableton Link sessions
+    pub clock: Arc<AsyncRwLock<ClockSource>>,
+    pub metronome: Arc<AsyncRwLock<Metronome>>,
     pub patterns: Arc<AsyncRwLock<HashMap<String, Pattern>>>,
     pub tracks: Arc<AsyncRwLock<HashMap<String, Track>>>,
     pub events: Arc<AsyncRwLock<HashMap<String, Event>>>,
@@ -23,19 +41,67 @@ pub struct Store {
 pub struct StrippedStore {
     pub name: String,
     pub bpm: f32,
-    pub target_addr: String,
+    #[serde(default = "StrippedStore::default_resolution")]
+    pub resolution: usize,
+    #[serde(default)]
+    pub time_signature: TimeSignature,
+    pub target_addr: String, // default target, kept for older project files
+    #[serde(default)]
+    pub targets: HashMap<String, Target>,
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    #[serde(default)]
+    pub delivery: DeliveryPolicies,
+    #[serde(default = "StrippedStore::default_slider_rate_hz")]
+    pub slider_rate_hz: f32,
+    #[serde(default)]
+    pub dmx: Vec<DmxMapping>,
+    #[serde(default)]
+    pub midi: Vec<MidiMapping>,
+    #[serde(default)]
+    pub sinks: HashMap<String, SinkOptions>,
+    #[serde(default)]
+    pub link: bool,
+    #[serde(default)]
+    pub clock: ClockSource,
+    #[serde(default)]
+    pub metronome: Metronome,
     pub patterns: HashMap<String, Pattern>,
     pub tracks: HashMap<String, Track>,
     pub events: HashMap<String, Event>,
     pub sliders: HashMap<String, Slider>,
 }
 
+impl StrippedStore {
+    fn default_resolution() -> usize {
+        DEFAULT_RESOLUTION
+    }
+
+    fn default_slider_rate_hz() -> f32 {
+        DEFAULT_SLIDER_RATE_HZ
+    }
+}
+
 impl From<StrippedStore> for Store {
-    fn from(val: StrippedStore) -> Self {
+    fn from(mut val: StrippedStore) -> Self {
+        val.targets
+            .entry(DEFAULT_TARGET_NAME.to_string())
+            .or_insert_with(|| Target::new(DEFAULT_TARGET_NAME.to_string(), val.target_addr));
         Store {
             name: Arc::new(AsyncRwLock::new(val.name)),
             bpm: Arc::new(AsyncRwLock::new(val.bpm)),
-            target_addr: Arc::new(AsyncRwLock::new(val.target_addr)),
+            resolution: Arc::new(AsyncRwLock::new(val.resolution)),
+            time_signature: Arc::new(AsyncRwLock::new(val.time_signature)),
+            targets: Arc::new(AsyncRwLock::new(val.targets)),
+            routes: Arc::new(AsyncRwLock::new(val.routes)),
+            delivery: Arc::new(AsyncRwLock::new(val.delivery)),
+            slider_rate_hz: Arc::new(AsyncRwLock::new(val.slider_rate_hz)),
+            dmx: Arc::new(AsyncRwLock::new(val.dmx)),
+            midi: Arc::new(AsyncRwLock::new(val.midi)),
+            sinks: Arc::new(AsyncRwLock::new(val.sinks)),
+            link: Arc::new(AsyncRwLock::new(val.link)),
+            clock: Arc::new(AsyncRwLock::new(val.clock)),
+            metronome: Arc::new(AsyncRwLock::new(val.metronome)),
             patterns: Arc::new(AsyncRwLock::new(val.patterns)),
             tracks: Arc::new(AsyncRwLock::new(val.tracks)),
             events: Arc::new(AsyncRwLock::new(val.events)),
@@ -49,7 +115,24 @@ impl Default for Store {
         Self {
             name: Arc::new(AsyncRwLock::new(DEFAULT_NAME.to_string())),
             bpm: Arc::new(AsyncRwLock::new(DEFAULT_BPM)),
-            target_addr: Arc::new(AsyncRwLock::new(DEFAULT_TARGET_ADDR.to_string())),
+            resolution: Arc::new(AsyncRwLock::new(DEFAULT_RESOLUTION)),
+            time_signature: Default::default(),
+            targets: Arc::new(AsyncRwLock::new(HashMap::from([(
+                DEFAULT_TARGET_NAME.to_string(),
+                Target::new(
+                    DEFAULT_TARGET_NAME.to_string(),
+                    DEFAULT_TARGET_ADDR.to_string(),
+                ),
+            )]))),
+            routes: Default::default(),
+            delivery: Default::default(),
+            slider_rate_hz: Arc::new(AsyncRwLock::new(DEFAULT_SLIDER_RATE_HZ)),
+            dmx: Default::default(),
+            midi: Default::default(),
+            sinks: Default::default(),
+            link: Default::default(),
+            clock: Default::default(),
+            metronome: Default::default(),
             patterns: Default::default(),
             tracks: Default::default(),
             events: Default::default(),
@@ -89,10 +172,26 @@ impl Store {
     }
 
     pub async fn snapshot(&self) -> StrippedStore {
+        let targets = self.targets.read().await.clone();
         StrippedStore {
             name: self.name.read().await.clone(),
             bpm: *self.bpm.read().await,
-            target_addr: self.target_addr.read().await.clone(),
+            resolution: *self.resolution.read().await,
+            time_signature: *self.time_signature.read().await,
+            target_addr: targets
+                .get(DEFAULT_TARGET_NAME)
+                .map(|target| target.addr.clone())
+                .unwrap_or_default(),
+            targets,
+            routes: self.routes.read().await.clone(),
+            delivery: self.delivery.read().await.clone(),
+            slider_rate_hz: *self.slider_rate_hz.read().await,
+            dmx: self.dmx.read().await.clone(),
+            midi: self.midi.read().await.clone(),
+            sinks: self.sinks.read().await.clone(),
+            link: *self.link.read().await,
+            clock: self.clock.read().await.clone(),
+            metronome: self.metronome.read().await.clone(),
             patterns: self.patterns.read().await.clone(),
             tracks: self.tracks.read().await.clone(),
             events: self.events.read().await.clone(),
@@ -0,0 +1,93 @@
+use std::{collections::HashMap, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::MIN_SLIDER_RATE_HZ;
+
+/// Coalesces values per key down to a maximum rate, the first one going out right away and the
+/// last one always getting through, at most one interval late
+#[derive(Debug)]
+pub struct Throttle<T> {
+    interval: Duration,
+    entries: HashMap<String, ThrottleEntry<T>>,
+}
+
+#[derive(Debug)]
+struct ThrottleEntry<T> {
+    last: Instant,
+    pending: Option<T>,
+}
+
+impl<T> Throttle<T> {
+    pub fn new(rate_hz: f32) -> Self {
+        let mut ret = Self {
+            interval: Duration::ZERO,
+            entries: HashMap::new(),
+        };
+        ret.set_rate(rate_hz);
+        ret
+    }
+
+    /// Zero (or anything not positive) lets everything through, slower rates than
+    /// [`MIN_SLIDER_RATE_HZ`] are raised to it
+    pub fn set_rate(&mut self, rate_hz: f32) {
+        self.interval = if rate_hz > 0.0 && rate_hz.is_finite() {
+            Duration::from_secs_f32(1.0 / rate_hz.max(MIN_SLIDER_RATE_HZ))
+        } else {
+            Duration::ZERO
+        };
+    }
+
+    /// Gives the value back if it may go out now, otherwise holds on to it until [`Self::pop_due`]
+    pub fn push(&mut self, key: &str, val: T) -> Option<T> {
+        let now = Instant::now();
+        match self.entries.get_mut(key) {
+            Some(entry) if now < entry.last + self.interval => {
+                // replaces whatever was held back
+                entry.pending = Some(val);
+                None
+            }
+            Some(entry) => {
+                entry.last = now;
+                entry.pending = None;
+                Some(val)
+            }
+            None => {
+                self.entries.insert(
+                    key.to_string(),
+                    ThrottleEntry {
+                        last: now,
+                        pending: None,
+                    },
+                );
+                Some(val)
+            }
+        }
+    }
+
+    pub fn next_due(&self) -> Option<Instant> {
+        self.entries
+            .values()
+            .filter(|entry| entry.pending.is_some())
+            .map(|entry| entry.last + self.interval)
+            .min()
+    }
+
+    /// Held values whose key hasn't sent anything for a whole interval
+    pub fn pop_due(&mut self) -> Vec<T> {
+        let now = Instant::now();
+        let mut ret = Vec::new();
+        for entry in self.entries.values_mut() {
+            if now >= entry.last + self.interval
+                && let Some(val) = entry.pending.take()
+            {
+                entry.last = now;
+                ret.push(val);
+            }
+        }
+        let interval = self.interval;
+        self.entries
+            .retain(|_, entry| entry.pending.is_some() || now < entry.last + interval);
+        ret
+    }
+}
@@ -1,13 +1,27 @@
-use std::{collections::HashMap, pin::pin, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    pin::pin,
+    sync::Arc,
+    time::Duration,
+};
 
+use serde::{Deserialize, Serialize};
 use tokio::{
     select,
-    sync::{RwLock as AsyncRwLock, mpsc, watch},
+    sync::{RwLock as AsyncRwLock, broadcast, mpsc, watch},
     time::{Instant, sleep_until},
 };
 use tracing::{info, warn};
 
-use crate::{controller::ControllerState, models::Pattern};
+use crate::{
+    DEFAULT_RESOLUTION, MAX_BPM, MIN_BPM,
+    ableton::AbletonCommand,
+    command::ClientCommand,
+    controller::ControllerState,
+    models::{Metronome, Pattern, Target, TimeSignature},
+    mosc::{MinOscArg, MinOscMessage},
+};
 
 #[derive(Debug)]
 pub enum TickerCommand {
@@ -15,20 +29,445 @@ pub enum TickerCommand {
     Pause,
     Stop,
     SetBPM { bpm: f32 },
+    Ramp { ramp: TempoRamp },
+    Tap { at: Instant },
+    Nudge { ms: i32 },
+    Follow { bpm: f32, beat: f64, at: Instant }, // a Link session's tempo, `beat` at `at`
+    Clock { event: ClockEvent, at: Instant },
+    ClockChanged, // wakes the ticker up, the clock may have been all that drove it
 }
 
 #[derive(Debug, Clone)]
 pub struct TickerState {
     pub patterns: Arc<AsyncRwLock<HashMap<String, Pattern>>>,
+    pub targets: Arc<AsyncRwLock<HashMap<String, Target>>>,
     pub bpm: Arc<AsyncRwLock<f32>>,
+    pub resolution: Arc<AsyncRwLock<usize>>, // project's steps per beat
+    pub time_signature: Arc<AsyncRwLock<TimeSignature>>,
     pub playing: Arc<AsyncRwLock<bool>>,
+    pub clock: Arc<AsyncRwLock<ClockSource>>,
+    pub metronome: Arc<AsyncRwLock<Metronome>>,
+    pub lateness: Arc<AsyncRwLock<TickLateness>>, // since playback last started from a stop
 }
 
 #[derive(Debug)]
 pub struct TickerArg {
     pub cmd_rx: mpsc::Receiver<TickerCommand>,
-    pub tick_tx: watch::Sender<(Option<usize>, usize)>,
+    pub tick_tx: watch::Sender<Tick>,
     pub controller_state: ControllerState,
+    pub ableton_cmd_tx: mpsc::Sender<AbletonCommand>,
+    pub client_cmd_broadcast_tx: broadcast::Sender<ClientCommand>,
+}
+
+/// Sent ahead of time by the largest target lookahead, `due` is when the tick should sound
+#[derive(Debug, Clone, Copy)]
+pub struct Tick {
+    pub tick: Option<usize>,
+    pub max: usize,
+    pub due: Instant,
+    pub resolution: usize,    // steps per beat `tick` counts
+    pub bar: usize,           // counted from 1 since playback started from a stop
+    pub click: Option<Click>, // also sent without a tick while counting in
+}
+
+impl Tick {
+    pub fn stopped() -> Self {
+        Self {
+            tick: None,
+            max: 0,
+            due: Instant::now(),
+            resolution: DEFAULT_RESOLUTION,
+            bar: 0,
+            click: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Click {
+    Accent, // first beat of the bar
+    Beat,
+}
+
+impl Click {
+    /// The click of a tick `bar_position` quarter notes into the bar, if it's on a beat
+    fn at(bar_position: f64, time_signature: &TimeSignature) -> Option<Self> {
+        let beats = bar_position / time_signature.unit_beats();
+        let nearest = beats.round();
+        if (beats - nearest).abs() > BAR_LINE_EPSILON {
+            return None;
+        }
+        // summed up in floats, the end of a bar is as good as its start
+        if nearest == 0.0 || nearest as usize >= time_signature.beats {
+            Some(Click::Accent)
+        } else {
+            Some(Click::Beat)
+        }
+    }
+}
+
+// LYN: Tap Tempo
+
+const TAP_TIMEOUT: Duration = Duration::from_secs(2); // a longer gap starts over
+const TAP_WINDOW: usize = 8;
+
+/// Tempo from the intervals between the last few taps
+#[derive(Debug, Default)]
+struct TapTempo {
+    taps: VecDeque<Instant>,
+}
+
+impl TapTempo {
+    /// The averaged BPM once there are at least two taps
+    fn tap(&mut self, at: Instant) -> Option<f32> {
+        // closer to the last tap than the fastest tempo allows, a bounce or a duplicate
+        if self
+            .taps
+            .back()
+            .is_some_and(|last| at.saturating_duration_since(*last).as_secs_f32() < 60.0 / MAX_BPM)
+        {
+            return None;
+        }
+        if self
+            .taps
+            .back()
+            .is_some_and(|last| at.saturating_duration_since(*last) > TAP_TIMEOUT)
+        {
+            self.taps.clear();
+        }
+        self.taps.push_back(at);
+        if self.taps.len() > TAP_WINDOW {
+            self.taps.pop_front();
+        }
+        let (first, last) = (self.taps.front()?, self.taps.back()?);
+        if self.taps.len() < 2 || first == last {
+            return None;
+        }
+        let average = (*last - *first).as_secs_f32() / (self.taps.len() - 1) as f32;
+        Some(60.0 / average).filter(|bpm| (MIN_BPM..=MAX_BPM).contains(bpm))
+    }
+}
+
+// LYN: External Clock
+
+const MIDI_CLOCK_PPQN: usize = 24;
+const TEMPO_WINDOW_BEATS: usize = 4;
+const TEMPO_REPORT_THRESHOLD: f32 = 0.5; // BPM, the estimate wobbles with network jitter
+const PULSE_TIMEOUT: Duration = Duration::from_secs(2); // a longer gap and the tempo is guessed anew
+const MAX_PPQN: usize = 96;
+const MIDI_TIMING_CLOCK: u8 = 0xf8;
+const MIDI_START: u8 = 0xfa;
+const MIDI_CONTINUE: u8 = 0xfb;
+const MIDI_STOP: u8 = 0xfc;
+
+/// What drives the ticks, the ticker's own timeline or clock messages coming in from targets
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ClockSource {
+    #[default]
+    Internal,
+    /// `ppqn` messages on `path` per beat, transport on `<path>/start`, `/stop` and `/continue`
+    Osc { path: String, ppqn: usize },
+    /// MIDI realtime bytes on `path`, as int args or blobs, 24 clocks per beat
+    Midi { path: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockEvent {
+    Pulse,
+    Start, // from the top
+    Continue,
+    Stop,
+}
+
+#[derive(Debug)]
+pub enum ClockSourceError {
+    PathNotAbsolute(String),
+    PpqnOutOfRange(usize),
+}
+
+impl fmt::Display for ClockSourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClockSourceError::PathNotAbsolute(path) => {
+                write!(f, "Path \"{}\" must start with \"/\"", path)
+            }
+            ClockSourceError::PpqnOutOfRange(ppqn) => write!(
+                f,
+                "{} pulses per beat is out of range, expecting 1 to {}",
+                ppqn, MAX_PPQN
+            ),
+        }
+    }
+}
+
+impl ClockSource {
+    pub fn validate(&self) -> Result<(), ClockSourceError> {
+        match self {
+            ClockSource::Internal => Ok(()),
+            ClockSource::Osc { path, .. } | ClockSource::Midi { path }
+                if !path.starts_with('/') =>
+            {
+                Err(ClockSourceError::PathNotAbsolute(path.clone()))
+            }
+            ClockSource::Osc { ppqn, .. } if !(1..=MAX_PPQN).contains(ppqn) => {
+                Err(ClockSourceError::PpqnOutOfRange(*ppqn))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn is_external(&self) -> bool {
+        *self != ClockSource::Internal
+    }
+
+    fn ppqn(&self) -> usize {
+        match self {
+            ClockSource::Internal => 0,
+            ClockSource::Osc { ppqn, .. } => *ppqn,
+            ClockSource::Midi { .. } => MIDI_CLOCK_PPQN,
+        }
+    }
+
+    /// What an incoming message means to the clock, nothing if it isn't one of its messages
+    pub fn events(&self, msg: &MinOscMessage) -> Vec<ClockEvent> {
+        match self {
+            ClockSource::Internal => vec![],
+            ClockSource::Osc { path, .. } => {
+                let event = match msg.path.strip_prefix(path.as_str()) {
+                    Some("") => ClockEvent::Pulse,
+                    Some("/start") => ClockEvent::Start,
+                    Some("/stop") => ClockEvent::Stop,
+                    Some("/continue") => ClockEvent::Continue,
+                    _ => return vec![],
+                };
+                vec![event]
+            }
+            ClockSource::Midi { path } if msg.path == *path => msg
+                .args
+                .iter()
+                .flat_map(|arg| match arg {
+                    MinOscArg::Blob(bytes) => bytes.clone(),
+                    arg => arg.as_f32().map(|byte| byte as u8).into_iter().collect(),
+                })
+                .filter_map(|byte| match byte {
+                    MIDI_TIMING_CLOCK => Some(ClockEvent::Pulse),
+                    MIDI_START => Some(ClockEvent::Start),
+                    MIDI_CONTINUE => Some(ClockEvent::Continue),
+                    MIDI_STOP => Some(ClockEvent::Stop),
+                    _ => None,
+                })
+                .collect(),
+            ClockSource::Midi { .. } => vec![],
+        }
+    }
+}
+
+/// Pulses counted into steps, the tempo guessed from how far apart they come in
+#[derive(Debug, Default)]
+struct ExternalClock {
+    pulse: u64,    // of the next pulse, since the clock last started
+    received: u64, // whether playing or not
+    recent: VecDeque<Instant>,
+}
+
+impl ExternalClock {
+    /// The BPM averaged over the last few beats' worth of pulses, once every beat
+    fn record(&mut self, at: Instant, ppqn: usize) -> Option<f32> {
+        if self
+            .recent
+            .back()
+            .is_some_and(|last| at.saturating_duration_since(*last) > PULSE_TIMEOUT)
+        {
+            self.recent.clear();
+        }
+        self.recent.push_back(at);
+        while self.recent.len() > ppqn * TEMPO_WINDOW_BEATS + 1 {
+            self.recent.pop_front();
+        }
+        self.received += 1;
+        if !self.received.is_multiple_of(ppqn as u64) {
+            return None;
+        }
+        let (first, last) = (self.recent.front()?, self.recent.back()?);
+        if self.recent.len() < 2 || first == last {
+            return None;
+        }
+        let pulse = (*last - *first).as_secs_f32() / (self.recent.len() - 1) as f32;
+        Some(60.0 / (pulse * ppqn as f32))
+    }
+
+    /// Moves on by a pulse, whether it starts a step at `resolution` steps per beat
+    fn advance(&mut self, ppqn: usize, resolution: usize) -> bool {
+        let step = |pulse: u64| pulse * resolution as u64 / ppqn as u64;
+        let starts_step = self.pulse == 0 || step(self.pulse) != step(self.pulse - 1);
+        self.pulse += 1;
+        starts_step
+    }
+}
+
+// LYN: Tempo Ramp
+
+const BAR_LINE_EPSILON: f64 = 1e-6; // beats are summed in floats
+
+/// Tempo change from the current BPM to `bpm`, spread over `length` beats or bars
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TempoRamp {
+    pub bpm: f32,
+    pub length: usize, // 0 jumps straight to `bpm`
+    pub unit: RampUnit,
+    pub curve: RampCurve,
+    #[serde(default)]
+    pub at_bar: Option<usize>, // starts on the first tick of that bar, right away if none
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RampUnit {
+    Beats,
+    Bars,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RampCurve {
+    Linear,
+    Exponential, // same ratio of change every beat, sounds even on wide ramps
+}
+
+#[derive(Debug)]
+pub enum TempoRampError {
+    InvalidBpm(f32),
+    BarOutOfRange,
+}
+
+impl fmt::Display for TempoRampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TempoRampError::InvalidBpm(bpm) => write!(
+                f,
+                "BPM {} is out of range, expecting {} to {}",
+                bpm, MIN_BPM, MAX_BPM
+            ),
+            TempoRampError::BarOutOfRange => write!(f, "Bars are counted from 1"),
+        }
+    }
+}
+
+impl TempoRamp {
+    pub fn validate(&self) -> Result<(), TempoRampError> {
+        if !(MIN_BPM..=MAX_BPM).contains(&self.bpm) {
+            return Err(TempoRampError::InvalidBpm(self.bpm));
+        }
+        if self.at_bar == Some(0) {
+            return Err(TempoRampError::BarOutOfRange);
+        }
+        Ok(())
+    }
+
+    fn start(&self, from: f32, bar_beats: f64) -> ActiveRamp {
+        ActiveRamp {
+            from,
+            to: self.bpm,
+            curve: self.curve,
+            length: match self.unit {
+                RampUnit::Beats => self.length as f64,
+                RampUnit::Bars => self.length as f64 * bar_beats,
+            },
+            elapsed: 0.0,
+        }
+    }
+}
+
+/// A ramp under way, lengths in beats
+#[derive(Debug)]
+struct ActiveRamp {
+    from: f32,
+    to: f32,
+    curve: RampCurve,
+    length: f64,
+    elapsed: f64,
+}
+
+impl ActiveRamp {
+    fn progress(&self) -> f32 {
+        if self.length <= 0.0 {
+            1.0
+        } else {
+            (self.elapsed / self.length).min(1.0) as f32
+        }
+    }
+
+    fn bpm(&self) -> f32 {
+        let progress = self.progress();
+        match self.curve {
+            RampCurve::Linear => self.from + (self.to - self.from) * progress,
+            RampCurve::Exponential => self.from * (self.to / self.from).powf(progress),
+        }
+    }
+}
+
+/// How late ticks went out compared to when they were scheduled, in microseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickLateness {
+    pub last_us: u64,
+    pub mean_us: u64,
+    pub max_us: u64,
+    pub ticks: u64,
+}
+
+impl TickLateness {
+    fn record(&mut self, late: Duration) {
+        let late_us = late.as_micros() as u64;
+        self.last_us = late_us;
+        self.max_us = self.max_us.max(late_us);
+        self.mean_us = (self.mean_us * self.ticks + late_us) / (self.ticks + 1);
+        self.ticks += 1;
+    }
+}
+
+/// Tick `n` is due at `start + n * interval`, no matter how long each tick took to process
+#[derive(Debug)]
+struct Timeline {
+    start: Instant,
+    interval: Duration,
+    count: u32,
+}
+
+impl Timeline {
+    /// Restarts the timeline with its next tick at `next`
+    fn anchor(next: Instant, interval: Duration) -> Self {
+        Self {
+            start: next,
+            interval,
+            count: 0,
+        }
+    }
+
+    fn next(&self) -> Instant {
+        self.start + self.interval * self.count
+    }
+
+    /// Shifts every upcoming tick, earlier for a positive `ms`
+    fn nudge(&mut self, ms: i32) {
+        let by = Duration::from_millis(ms.unsigned_abs() as u64);
+        if ms > 0 {
+            self.start = self.start.checked_sub(by).unwrap_or(self.start);
+        } else {
+            self.start += by;
+        }
+    }
+
+    /// Moves on to the next tick, skipping those that are already more than a tick late
+    fn advance(&mut self) {
+        self.count += 1;
+        let behind = Instant::now().saturating_duration_since(self.next());
+        if behind > self.interval {
+            let missed = (behind.as_nanos() / self.interval.as_nanos()) as u32;
+            warn!("Ticker fell behind, skipping {} ticks", missed);
+            self.count += missed;
+        }
+    }
 }
 
 pub async fn main(state: TickerState, arg: TickerArg) {
@@ -36,70 +475,69 @@ pub async fn main(state: TickerState, arg: TickerArg) {
 
     let TickerState {
         patterns,
+        targets,
         bpm,
+        resolution,
+        time_signature,
         playing,
+        clock,
+        metronome,
+        lateness,
     } = state;
     let TickerArg {
         mut cmd_rx,
         tick_tx,
         controller_state,
+        ableton_cmd_tx,
+        client_cmd_broadcast_tx,
     } = arg;
 
-    let mut interval = Duration::from_secs_f32(60.0 / (4.0 * *bpm.read().await));
-    let mut next_tick = Instant::now() + interval;
+    let mut step_resolution = *resolution.read().await;
+    let mut interval = step_interval(*bpm.read().await, step_resolution);
+    let mut timeline = Timeline::anchor(Instant::now() + interval, interval);
     let mut remaining = interval;
     let mut tick: Option<usize> = None;
+    let mut last_due: Option<Instant> = None;
+    let mut bar = 0;
+    let mut bar_position = 0.0; // beats into the bar
+    let mut last_limit = 0;
+    let mut ramp: Option<ActiveRamp> = None;
+    let mut scheduled: Vec<TempoRamp> = Vec::new();
+    let mut tap_tempo = TapTempo::default();
+    let mut external_clock = ExternalClock::default();
+    let mut source = ClockSource::Internal; // the clock the ticker last went by
+    let mut reported_bpm = *bpm.read().await; // last tempo clients heard of from the clock
+    let mut count_in = 0; // steps left
 
     loop {
-        let sleep_fut = sleep_until(next_tick);
+        let lookahead = targets
+            .read()
+            .await
+            .values()
+            .map(|target| Duration::from_millis(target.lookahead_ms))
+            .max()
+            .unwrap_or_default();
+        let due = timeline.next();
+        let wake = due.checked_sub(lookahead).unwrap_or(due);
+        let sleep_fut = sleep_until(wake);
         let mut sleep_fut = pin!(sleep_fut);
+        let mut fired = None; // due and wake time of a tick to send out
 
-        select! {
-            _ = &mut sleep_fut, if *playing.read().await => {
-                if tick.is_none() {
-                    tick = Some(0);
-                }
-                let pattern_name = controller_state.context.read().await;
-                if let Some(name) = &*pattern_name {
-                    // pattern
-                    let cycle = patterns.read().await.get(name).map(|pat| pat.page_count);
-                    match cycle {
-                        None => {
-                            warn!("Pattern not found: {}", name);
-                            next_tick = Instant::now() + interval;
-                            remaining = interval;
-                            continue;
-                        }
-                        Some(0) => {
-                            warn!("Pattern length is 0");
-                            next_tick = Instant::now() + interval;
-                            remaining = interval;
-                            continue;
-                        }
-                        Some(cycle) => {
-                            let limit = 4 * cycle - 1;
-                            if tick.unwrap() > limit {
-                                tick = Some(limit);
-                            }
-                            if let Err(err) = tick_tx.send((tick, limit)) {
-                                warn!("Ticker failed to send tick: {}", err);
-                            };
-                            tick = tick.map(|val| if val >= limit { 0 } else { val + 1 });
-                        }
-                    }
-                } else {
-                    // track
-                    if tick.unwrap() > 15 {
-                        tick = Some(15);
-                    }
-                    if let Err(err) = tick_tx.send((tick, 15)) {
-                        warn!("Ticker failed to send tick: {}", err);
-                    };
-                    tick = tick.map(|val| if val >= 15 { 0 } else { val + 1 });
-                };
+        // a new clock is listened to from scratch, and back on our own timeline it goes on
+        // from now
+        if *clock.read().await != source {
+            source = clock.read().await.clone();
+            external_clock = ExternalClock::default();
+            if !source.is_external() {
+                timeline = Timeline::anchor(Instant::now() + interval, interval);
+                continue;
+            }
+        }
+        let external = source.is_external();
 
-                next_tick = Instant::now() + interval;
-                remaining = interval;
+        select! {
+            _ = &mut sleep_fut, if !external && *playing.read().await => {
+                fired = Some((due, wake));
             }
 
             Some(cmd) = cmd_rx.recv() => {
@@ -108,34 +546,363 @@ pub async fn main(state: TickerState, arg: TickerArg) {
                         let mut playing = playing.write().await;
                         if !*playing {
                             *playing = true;
-                            next_tick = Instant::now() + remaining;
+                            if tick.is_none() && count_in == 0 {
+                                let bar_steps = time_signature.read().await.bar_steps(step_resolution);
+                                count_in = metronome.read().await.count_in * bar_steps;
+                                bar_position = 0.0;
+                            }
+                            timeline = Timeline::anchor(Instant::now() + remaining, interval);
+                            last_due = None;
+                            notify_ableton(&ableton_cmd_tx, AbletonCommand::Playing { playing: true });
                         }
                     }
                     TickerCommand::Pause => {
                         let mut playing = playing.write().await;
                         if *playing {
                             *playing = false;
-                            remaining = next_tick
+                            remaining = timeline
+                                .next()
                                 .saturating_duration_since(Instant::now());
+                            notify_ableton(&ableton_cmd_tx, AbletonCommand::Playing { playing: false });
                         }
                     }
                     TickerCommand::Stop => {
-                        *playing.write().await = false;
+                        let was_playing = std::mem::replace(&mut *playing.write().await, false);
+                        if was_playing {
+                            notify_ableton(&ableton_cmd_tx, AbletonCommand::Playing { playing: false });
+                        }
                         remaining = interval;
                         tick = None;
-                        if let Err(err) = tick_tx.send((None, 0)) {
+                        count_in = 0;
+                        last_due = None;
+                        ramp = None;
+                        scheduled.clear();
+                        if let Err(err) = tick_tx.send(Tick::stopped()) {
                             warn!("Ticker failed to send tick: {}", err);
                         };
                     }
                     TickerCommand::SetBPM { bpm: new_bpm } => {
+                        // an explicit tempo overrides any automation
+                        ramp = None;
+                        scheduled.clear();
                         let mut bpm = bpm.write().await;
                         *bpm = new_bpm;
-                        interval = Duration::from_secs_f32(60.0 / (4.0 * *bpm));
-                        next_tick = Instant::now() + interval;
+                        interval = step_interval(*bpm, step_resolution);
+                        // keep the groove, the next tick comes one new interval after the last
+                        // one rather than after the command
+                        let now = Instant::now();
+                        let next = last_due.map_or(now + interval, |due| (due + interval).max(now));
+                        timeline = Timeline::anchor(next, interval);
                         remaining = interval;
+                        let anchor = playing.read().await.then_some(next);
+                        notify_ableton(&ableton_cmd_tx, AbletonCommand::Tempo { bpm: new_bpm, anchor });
                     }
+                    TickerCommand::Tap { at } => {
+                        let Some(new_bpm) = tap_tempo.tap(at) else {
+                            continue;
+                        };
+                        ramp = None;
+                        scheduled.clear();
+                        *bpm.write().await = new_bpm;
+                        interval = step_interval(new_bpm, step_resolution);
+                        remaining = interval;
+                        // the tap was on a beat, the grid is laid on it
+                        let anchor = if *playing.read().await {
+                            let since = Instant::now().saturating_duration_since(at);
+                            let steps = (since.as_nanos() / interval.as_nanos()) as u32 + 1;
+                            timeline = Timeline::anchor(at + interval * steps, interval);
+                            Some(timeline.next())
+                        } else {
+                            None
+                        };
+                        notify_ableton(&ableton_cmd_tx, AbletonCommand::Tempo { bpm: new_bpm, anchor });
+                        broadcast(&client_cmd_broadcast_tx, ClientCommand::TickerBpmUpdated {
+                            bpm: new_bpm,
+                            time_signature: *time_signature.read().await,
+                        });
+                    }
+                    TickerCommand::Nudge { ms } => {
+                        if *playing.read().await {
+                            timeline.nudge(ms);
+                        }
+                    }
+                    TickerCommand::Follow { .. } if external => {}
+                    TickerCommand::Follow { bpm: new_bpm, beat: session_beat, at } => {
+                        // the session is in charge of the tempo, not our automation
+                        ramp = None;
+                        scheduled.clear();
+                        *bpm.write().await = new_bpm;
+                        interval = step_interval(new_bpm, step_resolution);
+                        remaining = interval;
+                        if *playing.read().await {
+                            // the next step of the session's grid, or its next bar line if we
+                            // haven't ticked yet
+                            let now = Instant::now();
+                            let beat_secs = 60.0 / new_bpm as f64;
+                            let now_beat = session_beat
+                                + (now.saturating_duration_since(at).as_secs_f64()) / beat_secs;
+                            let grid = if tick.is_some() {
+                                1.0 / step_resolution as f64
+                            } else {
+                                time_signature.read().await.bar_beats()
+                            };
+                            let next_beat = (now_beat / grid + BAR_LINE_EPSILON).ceil() * grid;
+                            let next = now + Duration::from_secs_f64((next_beat - now_beat) * beat_secs);
+                            timeline = Timeline::anchor(next, interval);
+                            last_due = None;
+                            if tick.is_some() {
+                                let step = (next_beat * step_resolution as f64).round() as usize;
+                                tick = Some(step % (last_limit + 1));
+                                let bar_beats = time_signature.read().await.bar_beats();
+                                bar_position = next_beat % bar_beats;
+                                if bar_position > bar_beats - BAR_LINE_EPSILON {
+                                    bar_position = 0.0;
+                                }
+                            }
+                        }
+                        broadcast(&client_cmd_broadcast_tx, ClientCommand::TickerBpmUpdated {
+                            bpm: new_bpm,
+                            time_signature: *time_signature.read().await,
+                        });
+                    }
+                    // NOTE: left over from a clock we've since switched away from
+                    TickerCommand::Clock { .. } if !external => {}
+                    TickerCommand::Clock { event: ClockEvent::Pulse, at } => {
+                        let ppqn = source.ppqn();
+                        if let Some(new_bpm) = external_clock.record(at, ppqn) {
+                            *bpm.write().await = new_bpm;
+                            interval = step_interval(new_bpm, step_resolution);
+                            remaining = interval;
+                            if (new_bpm - reported_bpm).abs() >= TEMPO_REPORT_THRESHOLD {
+                                reported_bpm = new_bpm;
+                                notify_ableton(&ableton_cmd_tx, AbletonCommand::Tempo { bpm: new_bpm, anchor: None });
+                                broadcast(&client_cmd_broadcast_tx, ClientCommand::TickerBpmUpdated {
+                                    bpm: new_bpm,
+                                    time_signature: *time_signature.read().await,
+                                });
+                            }
+                        }
+                        // NOTE: there's no knowing when the next pulse comes, ticks are due as
+                        // soon as theirs arrives and targets get no lookahead
+                        if *playing.read().await && external_clock.advance(ppqn, step_resolution) {
+                            fired = Some((at, at));
+                        }
+                    }
+                    TickerCommand::Clock { event, .. } => {
+                        let now_playing = event != ClockEvent::Stop;
+                        if event == ClockEvent::Start {
+                            tick = None;
+                            count_in = 0;
+                            ramp = None;
+                            scheduled.clear();
+                            external_clock.pulse = 0;
+                        }
+                        let was_playing = std::mem::replace(&mut *playing.write().await, now_playing);
+                        if was_playing != now_playing {
+                            notify_ableton(&ableton_cmd_tx, AbletonCommand::Playing { playing: now_playing });
+                        }
+                        broadcast(&client_cmd_broadcast_tx, if now_playing {
+                            ClientCommand::TickerPlaying
+                        } else {
+                            ClientCommand::TickerPaused
+                        });
+                    }
+                    // handed over at the top of the loop
+                    TickerCommand::ClockChanged => {}
+                    TickerCommand::Ramp { ramp: new_ramp } => {
+                        if new_ramp.at_bar.is_some() {
+                            scheduled.push(new_ramp);
+                        } else {
+                            let bar_beats = time_signature.read().await.bar_beats();
+                            ramp = Some(new_ramp.start(*bpm.read().await, bar_beats));
+                        }
+                    }
+                }
+            }
+        }
+
+        let Some((due, wake)) = fired else {
+            continue;
+        };
+
+        // counting in, clicks only, the first tick comes right after
+        if count_in > 0 {
+            let signature = *time_signature.read().await;
+            let bar_steps = signature.bar_steps(step_resolution);
+            if count_in.is_multiple_of(bar_steps) {
+                broadcast(
+                    &client_cmd_broadcast_tx,
+                    ClientCommand::TickerCountIn {
+                        bars_left: count_in / bar_steps,
+                    },
+                );
+            }
+            if let Err(err) = tick_tx.send(Tick {
+                tick: None,
+                max: 0,
+                due,
+                resolution: step_resolution,
+                bar: 0,
+                click: Click::at(bar_position, &signature),
+            }) {
+                warn!("Ticker failed to send tick: {}", err);
+            };
+            count_in -= 1;
+            bar_position += 1.0 / step_resolution as f64;
+            if bar_position > signature.bar_beats() - BAR_LINE_EPSILON {
+                bar_position = 0.0;
+            }
+            last_due = Some(due);
+            if external {
+                timeline = Timeline::anchor(due + interval, interval);
+            } else {
+                timeline.advance();
+            }
+            continue;
+        }
+        if tick.is_none() {
+            tick = Some(0);
+            bar = 1;
+            bar_position = 0.0;
+            *lateness.write().await = TickLateness::default();
+        }
+        let project_resolution = *resolution.read().await;
+        let pattern_name = controller_state.context.read().await;
+        let (limit, res) = if let Some(name) = &*pattern_name {
+            // pattern, at its own resolution
+            let cycle = patterns
+                .read()
+                .await
+                .get(name)
+                .map(|pat| (pat.page_count, pat.resolution(project_resolution)));
+            match cycle {
+                None => {
+                    warn!("Pattern not found: {}", name);
+                    timeline.advance();
+                    continue;
+                }
+                Some((0, _)) => {
+                    warn!("Pattern length is 0");
+                    timeline.advance();
+                    continue;
                 }
+                Some((cycle, res)) => (res * cycle - 1, res),
+            }
+        } else {
+            // track, a bar at the project's resolution
+            let bar = time_signature.read().await.bar_steps(project_resolution);
+            (bar - 1, project_resolution)
+        };
+
+        // the position carries over to the new grid, the interval only changes after
+        // this tick
+        let mut retime = res != step_resolution;
+        if retime {
+            tick = tick.map(|val| val * res / step_resolution);
+            step_resolution = res;
+        }
+        if tick.unwrap() > limit {
+            tick = Some(limit);
+        }
+        last_limit = limit;
+
+        // tempo changes scheduled for this bar start on its first tick
+        let bar_beats = time_signature.read().await.bar_beats();
+        if bar_position < BAR_LINE_EPSILON
+            && let Some(i) = scheduled
+                .iter()
+                .position(|sched| sched.at_bar.is_some_and(|at| at <= bar))
+        {
+            ramp = Some(scheduled.remove(i).start(*bpm.read().await, bar_beats));
+        }
+
+        let click = if metronome.read().await.enabled {
+            Click::at(bar_position, &*time_signature.read().await)
+        } else {
+            None
+        };
+
+        record_lateness(&lateness, wake).await;
+        if let Err(err) = tick_tx.send(Tick {
+            tick,
+            max: limit,
+            due,
+            resolution: res,
+            bar,
+            click,
+        }) {
+            warn!("Ticker failed to send tick: {}", err);
+        };
+        tick = tick.map(|val| if val >= limit { 0 } else { val + 1 });
+
+        let step_beats = 1.0 / step_resolution as f64;
+        if let Some(active) = &mut ramp {
+            active.elapsed += step_beats;
+            let new_bpm = active.bpm();
+            *bpm.write().await = new_bpm;
+            retime = true;
+            notify_ableton(
+                &ableton_cmd_tx,
+                AbletonCommand::Tempo {
+                    bpm: new_bpm,
+                    anchor: Some(due + step_interval(new_bpm, step_resolution)),
+                },
+            );
+            if active.progress() >= 1.0 {
+                ramp = None;
+                broadcast(
+                    &client_cmd_broadcast_tx,
+                    ClientCommand::TickerBpmUpdated {
+                        bpm: new_bpm,
+                        time_signature: *time_signature.read().await,
+                    },
+                );
+            } else {
+                broadcast(
+                    &client_cmd_broadcast_tx,
+                    ClientCommand::TickerRampProgress {
+                        bpm: new_bpm,
+                        target_bpm: active.to,
+                        progress: active.progress(),
+                    },
+                );
             }
         }
+        bar_position += step_beats;
+        if bar_position > bar_beats - BAR_LINE_EPSILON {
+            bar_position = 0.0;
+            bar += 1;
+        }
+
+        last_due = Some(due);
+        if retime || external {
+            interval = step_interval(*bpm.read().await, step_resolution);
+            timeline = Timeline::anchor(due + interval, interval);
+        } else {
+            timeline.advance();
+        }
+    }
+}
+
+fn broadcast(client_cmd_broadcast_tx: &broadcast::Sender<ClientCommand>, cmd: ClientCommand) {
+    if let Err(err) = client_cmd_broadcast_tx.send(cmd) {
+        warn!("Failed to broadcast client command: {}", err);
+    };
+}
+
+/// NOTE: never waits, Link sends the ticker commands too and both would block on a full channel
+fn notify_ableton(ableton_cmd_tx: &mpsc::Sender<AbletonCommand>, cmd: AbletonCommand) {
+    if let Err(err) = ableton_cmd_tx.try_send(cmd) {
+        warn!("Failed to notify Ableton Link: {}", err);
     }
 }
+
+fn step_interval(bpm: f32, resolution: usize) -> Duration {
+    Duration::from_secs_f32(60.0 / (resolution as f32 * bpm))
+}
+
+/// Lateness is measured against when the tick was meant to go out, lookahead included
+async fn record_lateness(lateness: &AsyncRwLock<TickLateness>, wake: Instant) {
+    let late = Instant::now().saturating_duration_since(wake);
+    lateness.write().await.record(late);
+}
@@ -0,0 +1,331 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::mosc::{MinOscArg, MinOscMessage};
+
+/// One step of a target's transform pipeline, applied to messages whose path matches `path`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransformRule {
+    #[serde(default = "TransformRule::any_path")]
+    pub path: String, // `*` matches any run of characters, `/` included
+    #[serde(flatten)]
+    pub op: TransformOp,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum TransformOp {
+    /// New path, each `*` taking what the matching `*` of the rule's path stood for
+    Rewrite {
+        to: String,
+    },
+    /// `value * scale + offset`, then clamped, ints stay ints (rounded)
+    Scale {
+        #[serde(default)]
+        arg: Option<usize>, // every numeric arg if none
+        #[serde(default = "TransformOp::unit")]
+        scale: f64,
+        #[serde(default)]
+        offset: f64,
+        #[serde(default)]
+        min: Option<f64>,
+        #[serde(default)]
+        max: Option<f64>,
+    },
+    /// Args that can't be converted are left as they are
+    Convert {
+        #[serde(default)]
+        arg: Option<usize>, // every arg if none
+        to: ArgType,
+    },
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArgType {
+    Int,
+    Float,
+    String,
+    Bool,
+    Long,
+    Double,
+}
+
+#[derive(Debug)]
+pub enum TransformError {
+    InvalidPath(String),
+    TooManyWildcards { path: String, to: String },
+    InvalidRange { min: f64, max: f64 },
+    NotFinite,
+}
+
+impl fmt::Display for TransformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransformError::InvalidPath(path) => {
+                write!(f, "Path \"{}\" must start with \"/\" or \"*\"", path)
+            }
+            TransformError::TooManyWildcards { path, to } => write!(
+                f,
+                "\"{}\" has more wildcards than \"{}\" captures",
+                to, path
+            ),
+            TransformError::InvalidRange { min, max } => {
+                write!(f, "Minimum {} is greater than maximum {}", min, max)
+            }
+            TransformError::NotFinite => write!(f, "Scale, offset and bounds must be finite"),
+        }
+    }
+}
+
+impl TransformRule {
+    fn any_path() -> String {
+        String::from("*")
+    }
+
+    pub fn validate(&self) -> Result<(), TransformError> {
+        if !self.path.starts_with(['/', '*']) {
+            return Err(TransformError::InvalidPath(self.path.clone()));
+        }
+        match &self.op {
+            TransformOp::Rewrite { to } => {
+                if !to.starts_with('/') {
+                    return Err(TransformError::InvalidPath(to.clone()));
+                }
+                if to.matches('*').count() > self.path.matches('*').count() {
+                    return Err(TransformError::TooManyWildcards {
+                        path: self.path.clone(),
+                        to: to.clone(),
+                    });
+                }
+            }
+            TransformOp::Scale {
+                scale,
+                offset,
+                min,
+                max,
+                ..
+            } => {
+                if [Some(*scale), Some(*offset), *min, *max]
+                    .into_iter()
+                    .flatten()
+                    .any(|v| !v.is_finite())
+                {
+                    return Err(TransformError::NotFinite);
+                }
+                if let (Some(min), Some(max)) = (*min, *max)
+                    && min > max
+                {
+                    return Err(TransformError::InvalidRange { min, max });
+                }
+            }
+            TransformOp::Convert { .. } | TransformOp::Drop => {}
+        }
+        Ok(())
+    }
+}
+
+impl TransformOp {
+    fn unit() -> f64 {
+        1.0
+    }
+}
+
+/// Runs the rules in order, later rules seeing the path earlier ones rewrote. `None` if dropped
+pub fn apply(rules: &[TransformRule], mut msg: MinOscMessage) -> Option<MinOscMessage> {
+    for rule in rules {
+        let Some(captures) = captures(&rule.path, &msg.path) else {
+            continue;
+        };
+        match &rule.op {
+            TransformOp::Rewrite { to } => {
+                let mut path = String::new();
+                let mut captures = captures.into_iter();
+                for (i, part) in to.split('*').enumerate() {
+                    if i > 0 {
+                        path.push_str(captures.next().unwrap_or_default());
+                    }
+                    path.push_str(part);
+                }
+                msg.path = path;
+            }
+            TransformOp::Scale {
+                arg,
+                scale,
+                offset,
+                min,
+                max,
+            } => {
+                let map = |v: f64| {
+                    let v = v * scale + offset;
+                    let v = min.map_or(v, |min| v.max(min));
+                    max.map_or(v, |max| v.min(max))
+                };
+                for val in selected(&mut msg.args, *arg) {
+                    *val = match *val {
+                        MinOscArg::Int(i) => MinOscArg::Int(map(i as f64).round() as i32),
+                        MinOscArg::Float(f) => MinOscArg::Float(map(f as f64) as f32),
+                        MinOscArg::Long(l) => MinOscArg::Long(map(l as f64).round() as i64),
+                        MinOscArg::Double(d) => MinOscArg::Double(map(d)),
+                        _ => continue,
+                    };
+                }
+            }
+            TransformOp::Convert { arg, to } => {
+                for val in selected(&mut msg.args, *arg) {
+                    if let Some(converted) = convert(val, *to) {
+                        *val = converted;
+                    }
+                }
+            }
+            TransformOp::Drop => return None,
+        }
+    }
+    Some(msg)
+}
+
+fn selected(args: &mut [MinOscArg], arg: Option<usize>) -> &mut [MinOscArg] {
+    match arg {
+        Some(i) if i < args.len() => &mut args[i..=i],
+        Some(_) => &mut [],
+        None => args,
+    }
+}
+
+fn convert(val: &MinOscArg, to: ArgType) -> Option<MinOscArg> {
+    let num = match val {
+        MinOscArg::String(s) => s.trim().parse::<f64>().ok(),
+        MinOscArg::Bool(b) => Some(*b as u8 as f64),
+        val => val.as_f64(),
+    };
+    Some(match to {
+        ArgType::Int => MinOscArg::Int(num?.round() as i32),
+        ArgType::Float => MinOscArg::Float(num? as f32),
+        ArgType::Long => MinOscArg::Long(num?.round() as i64),
+        ArgType::Double => MinOscArg::Double(num?),
+        ArgType::Bool => MinOscArg::Bool(match val {
+            MinOscArg::String(s) => !matches!(s.trim(), "" | "0" | "false"),
+            _ => num? != 0.0,
+        }),
+        ArgType::String => MinOscArg::String(match val {
+            MinOscArg::String(s) => s.clone(),
+            MinOscArg::Int(i) => i.to_string(),
+            MinOscArg::Float(f) => f.to_string(),
+            MinOscArg::Bool(b) => b.to_string(),
+            MinOscArg::Long(l) => l.to_string(),
+            MinOscArg::Double(d) => d.to_string(),
+            MinOscArg::Nil | MinOscArg::Blob(_) => return None,
+        }),
+    })
+}
+
+/// What each `*` of `pattern` matched in `path`, `None` if it doesn't match at all. Every `*`
+/// but the last one matches as little as it can
+fn captures<'a>(pattern: &str, path: &'a str) -> Option<Vec<&'a str>> {
+    let mut parts = pattern.split('*');
+    let mut rest = path.strip_prefix(parts.next().unwrap_or_default())?;
+    let parts = parts.collect::<Vec<_>>();
+    if parts.is_empty() {
+        return rest.is_empty().then(Vec::new);
+    }
+    let mut captures = Vec::with_capacity(parts.len());
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            captures.push(rest.strip_suffix(part)?);
+        } else {
+            let at = rest.find(part)?;
+            captures.push(&rest[..at]);
+            rest = &rest[at + part.len()..];
+        }
+    }
+    Some(captures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_captures() {
+        for (pattern, path, expected) in [
+            ("/a/b", "/a/b", Some(vec![])),
+            ("/a/b", "/a/bc", None),
+            ("/a/*", "/a/b/c", Some(vec!["b/c"])),
+            ("/a/*", "/a/", Some(vec![""])),
+            ("/a/*", "/b/c", None),
+            ("*", "", Some(vec![""])),
+            ("/*/x/*", "/foo/x/bar", Some(vec!["foo", "bar"])),
+            ("/*/x/*", "/x/x/", Some(vec!["x", ""])),
+            ("/*/*", "/a/b/c", Some(vec!["a", "b/c"])),
+            ("/*/*", "//", Some(vec!["", ""])),
+            ("**", "/x", Some(vec!["", "/x"])),
+            ("/a/*/b", "/a/b", None),
+            ("/*a*a", "/a", None),
+            ("/*a*a", "/aa", Some(vec!["", ""])),
+            ("/*-*-*", "/1-2-3-4", Some(vec!["1", "2", "3-4"])),
+        ] {
+            assert_eq!(captures(pattern, path), expected, "{} on {}", pattern, path);
+        }
+    }
+
+    fn rule(path: &str, op: TransformOp) -> TransformRule {
+        TransformRule {
+            path: path.to_string(),
+            op,
+        }
+    }
+
+    #[test]
+    fn rewrites_with_captures() {
+        let rules = [
+            rule(
+                "/*/note/*",
+                TransformOp::Rewrite {
+                    to: "/band2/*/n/*".to_string(),
+                },
+            ),
+            rule(
+                "/band2/*",
+                TransformOp::Rewrite {
+                    to: "/b2/*".to_string(),
+                },
+            ),
+        ];
+        let msg = MinOscMessage {
+            path: "/synth/note/".to_string(),
+            args: vec![],
+        };
+        assert_eq!(apply(&rules, msg).unwrap().path, "/b2/synth/n/");
+    }
+
+    #[test]
+    fn scales_and_drops() {
+        let rules = [
+            rule(
+                "/fader/*",
+                TransformOp::Scale {
+                    arg: Some(0),
+                    scale: 127.0,
+                    offset: 0.0,
+                    min: None,
+                    max: Some(100.0),
+                },
+            ),
+            rule("/mute", TransformOp::Drop),
+        ];
+        let msg = MinOscMessage {
+            path: "/fader/1".to_string(),
+            args: vec![MinOscArg::Int(1), MinOscArg::Float(0.5)],
+        };
+        assert_eq!(
+            apply(&rules, msg).unwrap().args,
+            vec![MinOscArg::Int(100), MinOscArg::Float(0.5)]
+        );
+        let msg = MinOscMessage {
+            path: "/mute".to_string(),
+            args: vec![],
+        };
+        assert!(apply(&rules, msg).is_none());
+    }
+}